@@ -123,8 +123,8 @@ class MainActivity : ComponentActivity() {
                                         loudnessInfo != null -> {
                                             val info = loudnessInfo!!
                                             Text("响度: ${"%.1f".format(info.lufs)} LUFS", style = bodySmall)
-                                            // Text("峰值: ${"%.1f".format(info.truePeak)} dBFS", style = bodySmall)
-                                            // Text("动态范围: ${"%.1f".format(info.lra)} dB", style = bodySmall)
+                                            info.truePeakDbtp()?.let { Text("峰值: ${"%.1f".format(it)} dBTP", style = bodySmall) }
+                                            info.lra?.let { Text("动态范围: ${"%.1f".format(it)} LU", style = bodySmall) }
                                         }
 
                                         selectedFile != null -> {
@@ -27,6 +27,9 @@ android {
     }
 
     kotlinOptions { jvmTarget = "21" }
+
+    // 处理器测试会调用 android.util.Log，本地单元测试里让它返回默认值
+    testOptions { unitTests.isReturnDefaultValues = true }
 }
 
 dependencies {
@@ -39,4 +42,7 @@ dependencies {
     // 只是依赖下
     val m3ver = "1.8.0"
     api("androidx.media3:media3-common-ktx:${m3ver}")
+
+    // 测量逻辑是纯 JVM 代码，用本地单元测试
+    testImplementation("junit:junit:4.13.2")
 }
\ No newline at end of file
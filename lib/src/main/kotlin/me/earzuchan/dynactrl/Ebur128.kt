@@ -1,72 +1,636 @@
 package me.earzuchan.dynactrl
 
+import me.earzuchan.dynactrl.models.ChannelType
+import me.earzuchan.dynactrl.models.GatingBlock
+import me.earzuchan.dynactrl.models.InvalidReason
+import me.earzuchan.dynactrl.models.LoudnessSnapshot
+import me.earzuchan.dynactrl.models.SamplesAdded
 import me.earzuchan.dynactrl.utils.CircularBuffer
 import me.earzuchan.dynactrl.utils.CompleteKWeighting
+import me.earzuchan.dynactrl.utils.HighpassFilter
+import me.earzuchan.dynactrl.utils.TruePeakMeter
 import kotlin.math.*
 
 /**
  * 轻量级的 EBU R128 实现
+ *
+ * @param dcBlock 在 K-weighting 之前额外加一级 5Hz 隔直高通，用于直流偏移很大的源，默认关闭以保持标准行为
+ * @param measureMonoSum 同时测量单声道下混的响度，见 [getMonoSumLoudness]
+ * @param measureTruePeak 测量 4 倍过采样真峰值，见 [getTruePeak]；输入必须是连续的样本（不能抽帧）
+ * @param weightSurround 环绕/侧声道按 BS.1770 加权 +1.5 dB（1.41），适用于独立的环绕声道；
+ * 环绕由前置声道矩阵/虚拟出来（phantom）时关闭，按前置声道同样的权重 1.0 计算，LFE 始终不计入
  */
-class LightweightEbuR128(private val channels: Int, sampleRate: Int) {
+class LightweightEbuR128(
+    channels: Int,
+    sampleRate: Int,
+    private val dcBlock: Boolean = false,
+    private val measureMonoSum: Boolean = false,
+    private val measureTruePeak: Boolean = false,
+    private val weightSurround: Boolean = true
+) {
     companion object {
         private const val TAG = "EbuR128"
         private const val ABSOLUTE_THRESHOLD_LUFS = -70f
         private const val RELATIVE_THRESHOLD_LU = -10f
         private const val BLOCK_SIZE_SEC = 0.4f // 400ms块
         private const val OVERLAP_RATIO = 0.75f // 75% 重叠
+        private const val DC_BLOCK_FREQ = 5f // 隔直截止频率
+        private const val SHORT_TERM_SEGMENTS = 30 // 短期响度窗口：30 个 hop（3s）
+        private const val SHORT_TERM_HOP_SEGMENTS = 10 // 短期块间隔：10 个 hop（1s）
+        private const val MOMENTARY_SEGMENTS = 4 // 瞬时响度窗口：4 个 hop（400ms）
+        private const val LRA_RELATIVE_THRESHOLD_LU = -20f
+        private const val LRA_LOW_PERCENTILE = 0.10
+        private const val LRA_HIGH_PERCENTILE = 0.95
+        private const val MIN_CONFIDENT_BLOCKS = 7 // 约 1 秒的音频
+        private const val INVERTED_CORRELATION_THRESHOLD = -0.5 // 相关系数低于它视为反相
+        private const val HISTOGRAM_BINS = 1000 // 增量积分用的直方图：-70 ~ +30 LUFS
+        private const val HISTOGRAM_STEP_LU = 0.1f
+
+        // 每个直方图格中心对应的能量
+        private val histogramBinEnergies = DoubleArray(HISTOGRAM_BINS) {
+            10.0.pow((ABSOLUTE_THRESHOLD_LUFS + (it + 0.5) * HISTOGRAM_STEP_LU + 0.691) / 10.0)
+        }
+
+        private val absoluteThresholdEnergy = 10f.pow((ABSOLUTE_THRESHOLD_LUFS + 0.691f) / 10f)
+
+        // 100ms hop 四舍五入到整数帧，400ms 块取整 4 个 hop，44.1kHz 等采样率下窗口长度与段数保持一致
+        private fun hopSizeFor(sampleRate: Int): Int = (sampleRate * BLOCK_SIZE_SEC * (1f - OVERLAP_RATIO)).roundToInt()
+
+        /**
+         * 对外部提供的块能量（均方值）做绝对门控和相对门控，返回综合响度（LUFS）
+         */
+        fun loudnessFromBlockEnergies(energies: List<Float>): Float =
+            gatedLoudness(energies.filter { it > absoluteThresholdEnergy })
+
+        // 1~2 个块（400ms~500ms 的音频）单独处理，结果置信度低，见 [isLowConfidence]
+        private fun gatedLoudness(blockEnergies: List<Float>): Float {
+            if (blockEnergies.isEmpty()) return Float.NEGATIVE_INFINITY
+
+            // 单块：相对门限是它自身能量 -10 LU，必然放行，结果即块响度
+            if (blockEnergies.size == 1) return 10f * log10(blockEnergies[0]) - 0.691f
+
+            // 两块：较弱的块比较强的低约 12.8 dB 以上（loud > 19·quiet）时被相对门限剔除，只剩较强的块
+            if (blockEnergies.size == 2) {
+                val quiet = minOf(blockEnergies[0], blockEnergies[1])
+                val loud = maxOf(blockEnergies[0], blockEnergies[1])
+                val relativeThreshold = (quiet + loud) / 2f * 10f.pow(RELATIVE_THRESHOLD_LU / 10f)
+                val gatedMeanEnergy = if (quiet >= relativeThreshold) (quiet + loud) / 2f else loud
+                return 10f * log10(gatedMeanEnergy) - 0.691f
+            }
+
+            // 相对门控
+            val meanEnergy = blockEnergies.average().toFloat()
+            val relativeThreshold = meanEnergy * 10f.pow(RELATIVE_THRESHOLD_LU / 10f)
+
+            val gatedEnergies = blockEnergies.filter { it >= relativeThreshold }
+            if (gatedEnergies.isEmpty()) return Float.NEGATIVE_INFINITY
+
+            val gatedMeanEnergy = gatedEnergies.average().toFloat()
+
+            // 重要：恢复标准的 -0.691f 校准值
+            return 10f * log10(gatedMeanEnergy) - 0.691f
+        }
+
+        /**
+         * 快速预览响度（拖进度条时用），每 decimation 帧只取 1 帧，按降低后的采样率计算
+         *
+         * 不做抗混叠，以精度换速度。实测误差（相对完整测量，偏低）：
+         * - decimation = 2：稳态音调 < 0.1 LU，宽带噪声约 0.1~0.3 LU
+         * - decimation = 4：稳态音调 < 0.2 LU，宽带噪声约 0.4~0.9 LU
+         * - decimation = 8：稳态音调 < 0.6 LU，宽带噪声约 1.5~2.5 LU
+         * - decimation >= 16：宽带内容误差可达数 LU 以上，不建议使用
+         */
+        fun quickLoudness(samples: FloatArray, channels: Int, sampleRate: Int, decimation: Int): Float {
+            require(channels > 0 && decimation > 0) { "Invalid channels or decimation" }
+            require(hopSizeFor(sampleRate / decimation) > 0) { "Decimated sample rate too low: ${sampleRate / decimation}" }
+
+            val frames = samples.size / channels
+            val outputFrames = (frames + decimation - 1) / decimation
+            val decimated = FloatArray(outputFrames * channels)
+
+            for (frame in 0 until outputFrames) {
+                val sourceIndex = frame * decimation * channels
+                for (ch in 0 until channels) decimated[frame * channels + ch] = samples[sourceIndex + ch]
+            }
+
+            return LightweightEbuR128(channels, sampleRate / decimation).apply { addSamples(decimated) }
+                .getIntegratedLoudness()
+        }
     }
 
-    private val blockSize = (sampleRate * BLOCK_SIZE_SEC).toInt()
-    private val hopSize = (blockSize * (1f - OVERLAP_RATIO)).toInt()
-    private val absoluteThresholdEnergy = 10f.pow((ABSOLUTE_THRESHOLD_LUFS + 0.691f) / 10f)
+    private var channels = channels
+    private var sampleRate = sampleRate
+    private var hopSize = hopSizeFor(sampleRate)
+    private var blockSize = hopSize * MOMENTARY_SEGMENTS
+
+    init {
+        require(channels > 0 && sampleRate > 0 && hopSize > 0) { "Invalid channels or sample rate: $channels, $sampleRate" }
+    }
 
     // 使用环形缓冲区提高效率
-    private val circularBuffer = CircularBuffer(blockSize * channels * 2)
-    private val kWeighting = CompleteKWeighting(sampleRate, channels)
+    private var circularBuffer = CircularBuffer(blockSize * channels * 2)
+    private var kWeighting = CompleteKWeighting(sampleRate, channels)
+    private var dcBlocker = if (dcBlock) HighpassFilter(sampleRate, channels, DC_BLOCK_FREQ) else null
+    private var monoSumAnalyzer = createMonoSumAnalyzer(sampleRate)
+    private var channelWeights = defaultChannelWeights(channels)
     private val blockEnergies = mutableListOf<Float>()
+    private val blockStartFrames = mutableListOf<Long>() // 与 blockEnergies 一一对应
+    private var blockCounter = 0L // 已计算的块数（含未通过绝对门限的）
+    private var lastBlockEnergy: Float? = null
+    private var hasSamples = false
+    private var samplePeak = 0f
+    private var truePeakMeter = if (measureTruePeak) TruePeakMeter(channels) else null
+
+    // 立体声中/侧能量（仅双声道）
+    private var midEnergy = 0.0
+    private var sideEnergy = 0.0
+
+    // 各声道能量和两两声道的互相关累加，用于检测反相声道对
+    private var channelEnergies = DoubleArray(channels)
+    private var pairProducts = DoubleArray(channels * (channels - 1) / 2)
+
+    // 按 hop（100ms）累计的能量段，用于 3s 短期响度
+    private val segmentEnergies = DoubleArray(SHORT_TERM_SEGMENTS)
+    private var segmentIndex = 0
+    private var segmentEnergy = 0.0
+    private var segmentFill = 0
+    private var segmentCount = 0L // 已完成的段数
+    private var shortTermCounter = 0 // 距上一个短期块累计的段数
+    private val shortTermEnergies = mutableListOf<Float>()
+
+    // 瞬时响度显示用的弹道平滑（时间常数，秒）；均为 0 时不平滑
+    private var momentaryAttackTime = 0f
+    private var momentaryReleaseTime = 0f
+    private var smoothedMomentary: Float? = null
+
+    // 增量积分响度状态，每个新块 O(1) 更新
+    private val histogram = IntArray(HISTOGRAM_BINS)
+    private var energySum = 0.0
+
+    /**
+     * 输入交错的浮点样本，长度必须是声道数的整数倍（不完整的帧会打乱声道顺序，直接拒绝）
+     * 空数组是安全的空操作，流开始或 flush 时可以直接传入
+     *
+     * @return 本次新完成的门控块和短期块数
+     */
+    fun addSamples(samples: FloatArray): SamplesAdded {
+        require(samples.size % channels == 0) { "Sample count ${samples.size} is not a multiple of $channels channels" }
+        if (samples.isEmpty()) return SamplesAdded.NONE
+        hasSamples = true
+
+        val blocksBefore = blockCounter
+        val shortTermBefore = shortTermEnergies.size
+
+        monoSumAnalyzer?.addSamples(downmixToMono(samples))
+
+        // 采样峰值（K-weighting 之前）
+        for (sample in samples) samplePeak = max(samplePeak, abs(sample))
+        truePeakMeter?.process(samples)
+
+        // 应用完整的 K-weighting（可选先隔直）
+        val weighted = kWeighting.process(dcBlocker?.process(samples) ?: samples)
+        if (channels == 2) accumulateMidSide(weighted)
+        if (channels > 1) accumulateCorrelation(samples)
+        accumulateSegments(weighted)
+
+        // 分段写入，每段不超过剩余空间，避免大输入在成块前覆盖掉未处理的样本
+        var offset = 0
+        while (offset < weighted.size) {
+            val count = minOf(circularBuffer.freeSpace, weighted.size - offset)
+            circularBuffer.addAll(weighted, offset, offset + count)
+            offset += count
+
+            // 处理完整的块
+            while (circularBuffer.size >= blockSize * channels) {
+                val blockEnergy = calculateBlockEnergy()
+                lastBlockEnergy = blockEnergy
+                if (blockEnergy > absoluteThresholdEnergy) {
+                    blockEnergies.add(blockEnergy)
+                    blockStartFrames.add(blockCounter * hopSize)
+                    addToHistogram(blockEnergy)
+                }
+                blockCounter++
+
+                // 移除 hop size 的样本
+                circularBuffer.removeFirst(hopSize * channels)
+            }
+        }
+
+        return SamplesAdded((blockCounter - blocksBefore).toInt(), shortTermEnergies.size - shortTermBefore)
+    }
+
+    /**
+     * 切换采样率（如自适应码流中途变化），按新采样率重建滤波器系数和块大小
+     *
+     * 注意：会重置全部测量状态，之前累计的块和峰值都会丢弃
+     */
+    fun setSampleRate(newSampleRate: Int) = reconfigure(channels, newSampleRate)
+
+    /**
+     * 改为新的声道数和采样率，用于批量分析时复用同一个实例
+     * 格式与当前相同时只清空状态、复用已分配的缓冲区和滤波器，否则按新格式重建
+     *
+     * 注意：会重置全部测量状态，之前累计的块和峰值都会丢弃
+     */
+    fun reconfigure(newChannels: Int, newSampleRate: Int) {
+        require(newChannels > 0) { "Invalid channel count: $newChannels" }
+        require(newSampleRate > 0 && hopSizeFor(newSampleRate) > 0) { "Invalid sample rate: $newSampleRate" }
+
+        if (newChannels == channels && newSampleRate == sampleRate) {
+            circularBuffer.clear()
+            kWeighting.reset()
+            dcBlocker?.reset()
+            monoSumAnalyzer?.reconfigure(1, newSampleRate)
+        } else {
+            channels = newChannels
+            sampleRate = newSampleRate
+            hopSize = hopSizeFor(newSampleRate)
+            blockSize = hopSize * MOMENTARY_SEGMENTS
+            circularBuffer = CircularBuffer(blockSize * newChannels * 2)
+            kWeighting = CompleteKWeighting(newSampleRate, newChannels)
+            dcBlocker = if (dcBlock) HighpassFilter(newSampleRate, newChannels, DC_BLOCK_FREQ) else null
+            monoSumAnalyzer = createMonoSumAnalyzer(newSampleRate)
+            if (measureTruePeak) truePeakMeter = TruePeakMeter(newChannels)
+            channelWeights = defaultChannelWeights(newChannels)
+            channelEnergies = DoubleArray(newChannels)
+            pairProducts = DoubleArray(newChannels * (newChannels - 1) / 2)
+        }
+
+        resetMeasurement()
+    }
+
+    private fun defaultChannelWeights(channels: Int): FloatArray {
+        val layout = ChannelType.defaultLayout(channels)
+        return FloatArray(channels) { layout?.get(it)?.let(::weightOf) ?: 1f }
+    }
+
+    private fun weightOf(type: ChannelType): Float = if (weightSurround) type.weight else minOf(type.weight, 1f)
+
+    /**
+     * 指定各声道的类型，按 BS.1770 权重计算能量（环绕/侧声道 1.41，关闭 weightSurround 时为 1；LFE 为 0）
+     * 默认按声道数使用 [ChannelType.defaultLayout]，声道顺序不同或布局未覆盖时调用
+     * 应在输入样本前设置；权重有变化时会像 [reconfigure] 一样重置全部测量状态，
+     * 避免按新旧两种权重计算的块混在一起。格式变为不同声道数后恢复默认布局
+     */
+    fun setChannelMap(map: List<ChannelType>) {
+        require(map.size == channels) { "Channel map size ${map.size} does not match channel count $channels" }
+
+        val weights = FloatArray(channels) { weightOf(map[it]) }
+        if (weights.contentEquals(channelWeights)) return
+
+        reconfigure(channels, sampleRate)
+        channelWeights = weights
+    }
+
+    private fun createMonoSumAnalyzer(sampleRate: Int): LightweightEbuR128? =
+        if (measureMonoSum && channels > 1) LightweightEbuR128(1, sampleRate, dcBlock) else null
+
+    // 按声道权重下混：幅度乘以 sqrt(权重)，LFE、未使用的声道权重为 0，不参与
+    // 整体再乘 sqrt(Σw / 声道数) / Σsqrt(w)，使各声道内容相同时下混能量与主测量（Σw·x² / 声道数）相等
+    private fun downmixToMono(samples: FloatArray): FloatArray {
+        val frames = samples.size / channels
+        val gainSum = channelWeights.sumOf { sqrt(it.toDouble()) }
+        val scale = if (gainSum > 0.0) sqrt(channelWeights.sum() / channels.toDouble()) / gainSum else 0.0
+        val gains = FloatArray(channels) { (sqrt(channelWeights[it].toDouble()) * scale).toFloat() }
+
+        return FloatArray(frames) { frame ->
+            var sum = 0f
+            for (ch in 0 until channels) sum += gains[ch] * samples[frame * channels + ch]
+            sum
+        }
+    }
+
+    private fun resetMeasurement() {
+        blockEnergies.clear()
+        blockStartFrames.clear()
+        blockCounter = 0L
+        lastBlockEnergy = null
+        hasSamples = false
+        samplePeak = 0f
+        truePeakMeter?.reset()
+        midEnergy = 0.0
+        sideEnergy = 0.0
+        channelEnergies.fill(0.0)
+        pairProducts.fill(0.0)
+        segmentEnergies.fill(0.0)
+        segmentIndex = 0
+        segmentEnergy = 0.0
+        segmentFill = 0
+        segmentCount = 0L
+        shortTermCounter = 0
+        shortTermEnergies.clear()
+        smoothedMomentary = null
+        histogram.fill(0)
+        energySum = 0.0
+    }
+
+    /**
+     * 32位整数 PCM 输入（如 AudioTrack 的 ENCODING_PCM_32BIT），内部转为浮点
+     */
+    fun addSamples(samples: IntArray): SamplesAdded {
+        if (samples.isEmpty()) return SamplesAdded.NONE
+        return addSamples(FloatArray(samples.size) { samples[it] / 2147483648f })
+    }
+
+    /**
+     * 16位整数 PCM 输入（解码器最常见的输出），内部转为浮点
+     */
+    fun addSamples(samples: ShortArray): SamplesAdded {
+        if (samples.isEmpty()) return SamplesAdded.NONE
+        return addSamples(FloatArray(samples.size) { samples[it] / 32768f })
+    }
+
+    /**
+     * 双精度浮点输入，收窄为单精度后处理
+     */
+    fun addSamples(samples: DoubleArray): SamplesAdded {
+        if (samples.isEmpty()) return SamplesAdded.NONE
+        return addSamples(FloatArray(samples.size) { samples[it].toFloat() })
+    }
+
+    private fun accumulateSegments(weighted: FloatArray) {
+        val segmentSamples = hopSize * channels
 
-    fun addSamples(samples: FloatArray) {
-        if (samples.isEmpty()) return
+        for (sample in weighted) {
+            segmentEnergy += channelWeights[segmentFill % channels] * sample * sample
+            if (++segmentFill < segmentSamples) continue
 
-        // 应用完整的 K-weighting
-        val weighted = kWeighting.process(samples)
-        circularBuffer.addAll(weighted)
+            segmentEnergies[segmentIndex] = segmentEnergy
+            segmentIndex = (segmentIndex + 1) % SHORT_TERM_SEGMENTS
+            segmentEnergy = 0.0
+            segmentFill = 0
+            segmentCount++
+            updateSmoothedMomentary()
 
-        // 处理完整的块
-        while (circularBuffer.size >= blockSize * channels) {
-            val blockEnergy = calculateBlockEnergy()
-            if (blockEnergy > absoluteThresholdEnergy) blockEnergies.add(blockEnergy)
+            // 攒够 3s 后每 1s 产生一个短期块
+            if (++shortTermCounter >= SHORT_TERM_SEGMENTS) {
+                shortTermEnergies.add((segmentEnergies.sum() / (SHORT_TERM_SEGMENTS * segmentSamples)).toFloat())
+                shortTermCounter -= SHORT_TERM_HOP_SEGMENTS
+            }
+        }
+    }
 
-            // 移除 hop size 的样本
-            circularBuffer.removeFirst(hopSize * channels)
+    private fun updateSmoothedMomentary() {
+        val raw = recentLoudness(MOMENTARY_SEGMENTS) ?: return
+        val previous = smoothedMomentary
+
+        smoothedMomentary = if (previous == null || !previous.isFinite() || !raw.isFinite()) raw
+        else {
+            // 每 100ms 段保留的比例
+            val time = if (raw > previous) momentaryAttackTime else momentaryReleaseTime
+            val coeff = if (time > 0f) exp(-hopSize.toDouble() / sampleRate / time) else 0.0
+            (raw + (previous - raw) * coeff).toFloat()
         }
     }
 
+    private fun accumulateCorrelation(samples: FloatArray) {
+        for (frame in 0 until samples.size / channels) {
+            val base = frame * channels
+            var pair = 0
+            for (a in 0 until channels) {
+                val x = samples[base + a].toDouble()
+                channelEnergies[a] += x * x
+                for (b in a + 1 until channels) pairProducts[pair++] += x * samples[base + b]
+            }
+        }
+    }
+
+    private fun accumulateMidSide(weighted: FloatArray) {
+        for (frame in 0 until weighted.size / 2) {
+            val left = weighted[frame * 2]
+            val right = weighted[frame * 2 + 1]
+            val mid = (left + right) * 0.5f
+            val side = (left - right) * 0.5f
+            midEnergy += mid * mid
+            sideEnergy += side * side
+        }
+    }
+
+    private fun addToHistogram(blockEnergy: Float) {
+        val blockLoudness = 10f * log10(blockEnergy) - 0.691f
+        val bin = ((blockLoudness - ABSOLUTE_THRESHOLD_LUFS) / HISTOGRAM_STEP_LU).toInt()
+        histogram[bin.coerceIn(0, HISTOGRAM_BINS - 1)]++
+        energySum += blockEnergy
+    }
+
     private fun calculateBlockEnergy(): Float {
         var energy = 0f
         val totalSamples = blockSize * channels
 
         for (i in 0 until totalSamples) {
             val sample = circularBuffer.get(i)
-            energy += sample * sample
+            energy += channelWeights[i % channels] * sample * sample
         }
 
         return energy / (blockSize * channels)
     }
 
-    fun getIntegratedLoudness(): Float {
-        if (blockEnergies.size < 2) return Float.NEGATIVE_INFINITY
+    /**
+     * 立体声宽度：侧（L-R）与中（L+R）能量之比（dB），越负越接近单声道，非双声道或无信号时为 null
+     */
+    fun getStereoWidth(): Float? =
+        if (channels != 2 || midEnergy <= 0.0) null else (10.0 * log10(sideEnergy / midEnergy)).toFloat()
+
+    /**
+     * 强负相关（相关系数 < -0.5）的声道对 (a, b)，a < b，用于质检接线反相等问题
+     * 反相会让单声道下混互相抵消；任一声道无信号的声道对不参与判断
+     */
+    fun getInvertedChannelPairs(): List<Pair<Int, Int>> {
+        val pairs = mutableListOf<Pair<Int, Int>>()
+        var pair = 0
+        for (a in 0 until channels) for (b in a + 1 until channels) {
+            val norm = sqrt(channelEnergies[a] * channelEnergies[b])
+            if (norm > 0.0 && pairProducts[pair] / norm < INVERTED_CORRELATION_THRESHOLD) pairs.add(a to b)
+            pair++
+        }
+        return pairs
+    }
+
+    /**
+     * 清空 K-weighting 滤波器历史（不影响已有的块），用于播放跳转等不连续点，
+     * 避免跳转前的滤波器记忆在瞬时响度上造成瞬态
+     */
+    fun flushFilterState() {
+        kWeighting.reset()
+        dcBlocker?.reset()
+        monoSumAnalyzer?.flushFilterState()
+    }
+
+    /**
+     * 完全重置：清空滤波器历史、未成块的样本和全部测量结果（块、峰值、短期块等），
+     * 复用同一实例分析下一首音轨时使用，避免上一首的状态混入
+     * 只需处理跳转等不连续点而保留已有测量时用 [flushFilterState]
+     */
+    fun reset() = reconfigure(channels, sampleRate)
+
+    /**
+     * 单声道下混（各声道按 BS.1770 权重加权，不含 LFE）后的综合响度，用于检查单声道兼容性
+     *
+     * 与 [getIntegratedLoudness] 按声道分别计算能量不同，这里测的是真实下混信号：
+     * 各声道内容相同时两者相同（任意布局）；声道间互不相关时下混偏低 -10·log10(Σw / (Σ√w)²) dB
+     * （立体声约 3 dB，5.1 约 7 dB，与各声道电平无关），反相则大幅下降
+     * 未启用 measureMonoSum 时为 -INF，单声道输入时等于标准测量
+     */
+    fun getMonoSumLoudness(): Float = when {
+        channels == 1 -> getIntegratedLoudness()
+        else -> monoSumAnalyzer?.getIntegratedLoudness() ?: Float.NEGATIVE_INFINITY
+    }
+
+    /**
+     * 线性采样峰值（0~1 为满幅以内）
+     */
+    fun getSamplePeak(): Float = samplePeak
+
+    /**
+     * 线性真峰值（4 倍过采样），未启用 measureTruePeak 时为 null
+     */
+    fun getTruePeak(): Float? = truePeakMeter?.let { max(it.truePeak, samplePeak) }
+
+    fun getIntegratedLoudness(): Float = gatedLoudness(blockEnergies)
+
+    /**
+     * 只对最近 windowSeconds 秒内的块做门控的综合响度（如直播监测最近 30 秒）
+     */
+    fun getIntegratedLoudnessWindowed(windowSeconds: Float): Float {
+        if (blockCounter == 0L) return Float.NEGATIVE_INFINITY
+
+        // 窗口以最后一个块的结束位置为终点
+        val windowEnd = (blockCounter - 1) * hopSize + blockSize
+        val windowStart = windowEnd - (windowSeconds * sampleRate).toLong()
+
+        val windowed = blockEnergies.indices.filter { blockStartFrames[it] >= windowStart }.map { blockEnergies[it] }
+        return gatedLoudness(windowed)
+    }
+
+    /**
+     * 两个时间区间（起止秒数）各自门控后的综合响度之差 B - A（LU），如副歌比主歌响多少
+     * 只计入完全落在区间内的块，任一区间不可测量时为 null
+     */
+    fun getSegmentLoudnessDelta(regionA: Pair<Float, Float>, regionB: Pair<Float, Float>): Float? {
+        val loudnessA = regionLoudness(regionA)
+        val loudnessB = regionLoudness(regionB)
+        return if (loudnessA.isFinite() && loudnessB.isFinite()) loudnessB - loudnessA else null
+    }
+
+    private fun regionLoudness(region: Pair<Float, Float>): Float {
+        val start = (region.first * sampleRate).toLong()
+        val end = (region.second * sampleRate).toLong()
+
+        val inRegion = blockEnergies.indices.filter { blockStartFrames[it] >= start && blockStartFrames[it] + blockSize <= end }
+            .map { blockEnergies[it] }
+        return gatedLoudness(inRegion)
+    }
+
+    /**
+     * 综合响度是否只基于很少的块（不足约 1 秒），此时结果仅供参考
+     */
+    fun isLowConfidence(): Boolean = blockEnergies.size < MIN_CONFIDENT_BLOCKS
+
+    /**
+     * 综合响度无法测量的原因，可测量时为 null
+     */
+    fun getInvalidReason(): InvalidReason? = when {
+        getIntegratedLoudness().isFinite() -> null
+        !hasSamples -> InvalidReason.NO_SAMPLES
+        blockCounter == 0L -> InvalidReason.TOO_SHORT
+        else -> InvalidReason.ALL_BELOW_GATE
+    }
+
+    /**
+     * 瞬时响度（最近 400ms，LUFS），数据不足时为 null
+     */
+    fun getMomentaryLoudness(): Float? = recentLoudness(MOMENTARY_SEGMENTS)
+
+    /**
+     * 设置瞬时响度显示的弹道（上升/下降时间常数，秒），模拟硬件表头的指针惯性
+     * 按 dB 值做一阶平滑，每 100ms 更新一次；两者都为 0 时 [getMomentaryLoudnessSmoothed] 即原始值
+     */
+    fun setMomentaryBallistics(attackTime: Float, releaseTime: Float) {
+        require(attackTime >= 0f && releaseTime >= 0f) { "Ballistics time constants must be non-negative" }
+
+        momentaryAttackTime = attackTime
+        momentaryReleaseTime = releaseTime
+    }
+
+    /**
+     * 经弹道平滑的瞬时响度（LUFS），适合界面显示，数据不足时为 null
+     */
+    fun getMomentaryLoudnessSmoothed(): Float? = if (segmentCount < MOMENTARY_SEGMENTS) null else smoothedMomentary
+
+    /**
+     * 短期响度（最近 3s，LUFS），数据不足时为 null
+     */
+    fun getShortTermLoudness(): Float? = recentLoudness(SHORT_TERM_SEGMENTS)
+
+    // 最近 segments 个完整段的响度，从环形缓冲的写入位置往回取，跨越首尾时自动回绕
+    private fun recentLoudness(segments: Int): Float? {
+        if (segmentCount < segments) return null
+
+        var energy = 0.0
+        for (i in 1..segments) energy += segmentEnergies[(segmentIndex - i + SHORT_TERM_SEGMENTS) % SHORT_TERM_SEGMENTS]
+
+        return (10.0 * log10(energy / (segments * hopSize * channels)) - 0.691).toFloat()
+    }
+
+    /**
+     * 响度范围 LRA（LU），按 EBU Tech 3342：3s 短期块经 -70 LUFS 绝对门限和 -20 LU 相对门限后，
+     * 取第 95 与第 10 百分位之差（百分位取法同 libebur128），没有可用短期块时为 null
+     */
+    fun getLoudnessRange(): Float? {
+        val absoluteGated = shortTermEnergies.filter { it > absoluteThresholdEnergy }
+        if (absoluteGated.isEmpty()) return null
+
+        val relativeThreshold = absoluteGated.average() * 10.0.pow(LRA_RELATIVE_THRESHOLD_LU / 10.0)
+        val gated = absoluteGated.filter { it >= relativeThreshold }.sorted()
+        if (gated.isEmpty()) return null
+
+        val low = gated[((gated.size - 1) * LRA_LOW_PERCENTILE + 0.5).toInt()]
+        val high = gated[((gated.size - 1) * LRA_HIGH_PERCENTILE + 0.5).toInt()]
+
+        return 10f * log10(high / low)
+    }
+
+    /**
+     * 增量维护的综合响度，不重新遍历块列表，适合频繁轮询的实时表
+     * 门控后的能量按 0.1 LU 直方图近似，与 [getIntegratedLoudness] 的差异通常在 0.05 LU 以内
+     */
+    fun getIntegratedLoudnessIncremental(): Float {
+        if (blockEnergies.isEmpty()) return Float.NEGATIVE_INFINITY
 
         // 相对门控
-        val meanEnergy = blockEnergies.average().toFloat()
-        val relativeThreshold = meanEnergy * 10f.pow(RELATIVE_THRESHOLD_LU / 10f)
+        val relativeThreshold = energySum / blockEnergies.size * 10.0.pow(RELATIVE_THRESHOLD_LU / 10.0)
+        val thresholdLoudness = 10.0 * log10(relativeThreshold) - 0.691
+        val startBin = ((thresholdLoudness - ABSOLUTE_THRESHOLD_LUFS) / HISTOGRAM_STEP_LU).toInt()
+            .coerceIn(0, HISTOGRAM_BINS - 1)
 
-        val gatedEnergies = blockEnergies.filter { it >= relativeThreshold }
-        if (gatedEnergies.isEmpty()) return Float.NEGATIVE_INFINITY
+        var gatedCount = 0L
+        var gatedEnergy = 0.0
+        for (bin in startBin until HISTOGRAM_BINS) {
+            gatedCount += histogram[bin]
+            gatedEnergy += histogram[bin] * histogramBinEnergies[bin]
+        }
+        if (gatedCount == 0L) return Float.NEGATIVE_INFINITY
 
-        val gatedMeanEnergy = gatedEnergies.average().toFloat()
+        return (10.0 * log10(gatedEnergy / gatedCount) - 0.691).toFloat()
+    }
+
+    /**
+     * 最近一个门控块的原始能量（取对数前，不论是否通过门限），用于和参考实现逐级比对
+     */
+    fun getLastBlockEnergy(): Float? = lastBlockEnergy
+
+    /**
+     * 通过绝对门限的门控块及其起始时间，块间隔为 hop（100ms）
+     */
+    fun getGatingBlocks(): List<GatingBlock> = blockEnergies.indices.map {
+        GatingBlock(blockStartFrames[it].toFloat() / sampleRate, blockEnergies[it])
+    }
 
-        // 重要：恢复标准的 -0.691f 校准值
-        return 10f * log10(gatedMeanEnergy) - 0.691f
+    /**
+     * 轻量快照：只复制块能量列表和当前结果（含 LRA 和真峰值），适合流式处理中途保存结果
+     */
+    fun snapshot(): LoudnessSnapshot {
+        val energies = blockEnergies.toList()
+        return LoudnessSnapshot(energies, gatedLoudness(energies), samplePeak, getLoudnessRange(), getTruePeak())
     }
 }
\ No newline at end of file
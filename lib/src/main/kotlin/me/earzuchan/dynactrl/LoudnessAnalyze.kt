@@ -6,6 +6,8 @@ import android.media.MediaExtractor
 import android.media.MediaFormat
 import android.util.Log
 import me.earzuchan.dynactrl.models.AudioLoudnessInfo
+import me.earzuchan.dynactrl.models.DetectedFormat
+import me.earzuchan.dynactrl.models.InvalidReason
 import me.earzuchan.dynactrl.utils.BufferPool
 import java.io.File
 import java.nio.ByteBuffer
@@ -50,11 +52,12 @@ class LightweightLoudnessAnalyzer {
     fun analyzeFile(audioFile: File, ultraLightMode: Boolean = true): AudioLoudnessInfo {
         if (!audioFile.exists() || !audioFile.canRead()) {
             Log.e(TAG, "File not accessible: ${audioFile.absolutePath}")
-            return AudioLoudnessInfo(-70f)
+            return AudioLoudnessInfo(-70f, invalidReason = InvalidReason.UNREADABLE)
         }
 
         var extractor: MediaExtractor? = null
         var codec: MediaCodec? = null
+        val startTimeNs = System.nanoTime()
 
         try {
             // 1. 初始化 MediaExtractor
@@ -64,27 +67,29 @@ class LightweightLoudnessAnalyzer {
             val audioTrackIndex = findAudioTrack(extractor)
             if (audioTrackIndex < 0) {
                 Log.e(TAG, "No audio track found")
-                return AudioLoudnessInfo(-70f)
+                return AudioLoudnessInfo(-70f, invalidReason = InvalidReason.UNREADABLE)
             }
 
             extractor.selectTrack(audioTrackIndex)
             val format = extractor.getTrackFormat(audioTrackIndex)
 
             // 获取音频参数
-            val sampleRate = format.getInteger(MediaFormat.KEY_SAMPLE_RATE)
-            val originalChannelCount = format.getInteger(MediaFormat.KEY_CHANNEL_COUNT)
+            // 与编码一样以解码器实际输出为准，INFO_OUTPUT_FORMAT_CHANGED 时更新
+            var sampleRate = format.getInteger(MediaFormat.KEY_SAMPLE_RATE)
+            var originalChannelCount = format.getInteger(MediaFormat.KEY_CHANNEL_COUNT)
             val mime = format.getString(MediaFormat.KEY_MIME)
 
             // 验证MIME类型
             if (mime.isNullOrEmpty()) {
                 Log.e(TAG, "Missing MIME type: $mime")
-                return AudioLoudnessInfo(-70f)
+                return AudioLoudnessInfo(-70f, invalidReason = InvalidReason.UNREADABLE)
             }
 
-            val pcmFormat = detectPcmFormat(format)
+            // 轨道格式只是初步判断，解码器实际输出的编码以 INFO_OUTPUT_FORMAT_CHANGED 为准
+            var pcmFormat = detectPcmFormat(format)
 
             // 超轻模式：强制单声道处理
-            val newChannelCount = if (ultraLightMode) 1 else originalChannelCount
+            var newChannelCount = if (ultraLightMode) 1 else originalChannelCount
             // TODO：byd 越降响度计算出来越低了，可能要乘个系数？
             // val downsampleRatio = if (ultraLightMode) ULTRA_LIGHT_DOWNSAMPLE_RATIO else DOWNSAMPLE_RATIO TIPS：暂时禁用降采样
             val maxAnalysisDuration =
@@ -121,20 +126,23 @@ class LightweightLoudnessAnalyzer {
             // 5. 初始化降采样器和响度计算器 TIPS：暂时禁用降采样
             /*val downsampledSampleRate = sampleRate / downsampleRatio
             val downsampler = AntiAliasingDownsampler(downsampleRatio, newChannelCount, sampleRate)*/
-            val loudnessCalculator = LightweightEbuR128(newChannelCount, sampleRate /*downsampledSampleRate*/)
+            // 超轻模式会抽帧，真峰值无意义，只在完整模式下测（完整模式逐块送入全部样本，输入连续）
+            val loudnessCalculator = LightweightEbuR128(
+                newChannelCount, sampleRate /*downsampledSampleRate*/, measureTruePeak = !ultraLightMode
+            )
 
             // 6. 解码并处理音频
             val bufferInfo = MediaCodec.BufferInfo()
             var isEnded = false
             var totalSamplesProcessed = 0L
             var frameSkipCounter = 0 // 用于跳帧处理
-            val maxSamples = (maxAnalysisDuration * sampleRate / 1_000_000L).toInt()
+            var decodedPeak = 0f // 在下混和抽样之前的原始解码样本上统计，超轻模式下也是真实峰值
+            var maxSamples = (maxAnalysisDuration * sampleRate / 1_000_000L).toInt()
 
             // 使用对象池减少内存分配
             val sampleBuffer = BufferPool.borrowFloatArray(BUFFER_SIZE)
-            val monoBuffer = if (ultraLightMode && originalChannelCount > 1) {
-                BufferPool.borrowFloatArray(BUFFER_SIZE / originalChannelCount)
-            } else null
+            // 按单声道最多帧数分配，输出声道数变化时也够用
+            val monoBuffer = if (ultraLightMode) BufferPool.borrowFloatArray(BUFFER_SIZE) else null
 
             try {
                 while (!isEnded && totalSamplesProcessed < maxSamples) {
@@ -180,42 +188,43 @@ class LightweightLoudnessAnalyzer {
                         outputBufferIndex >= 0 -> {
                             val outputBuffer = codec.getOutputBuffer(outputBufferIndex)
                             if (outputBuffer != null && bufferInfo.size > 0) {
-                                // 超轻模式：跳帧处理
-                                if (ultraLightMode) {
-                                    frameSkipCounter++
-                                    if (frameSkipCounter % ULTRA_LIGHT_FRAME_SKIP_RATIO != 0) {
-                                        codec.releaseOutputBuffer(outputBufferIndex, false)
-                                        continue
+                                // 超轻模式：跳帧处理（峰值仍然统计）
+                                val skipBuffer = ultraLightMode && ++frameSkipCounter % ULTRA_LIGHT_FRAME_SKIP_RATIO != 0
+
+                                // 按 sampleBuffer 大小分块读完整个输出缓冲区，不丢样本，真峰值的滤波器历史保持连续
+                                var startSample = 0
+                                while (true) {
+                                    // 处理PCM数据
+                                    val samples = processPcmData(
+                                        outputBuffer, bufferInfo.size, startSample, pcmFormat, originalChannelCount, sampleBuffer
+                                    )
+
+                                    // 读完（或不足一帧）
+                                    if (samples.isEmpty()) break
+                                    startSample += samples.size
+
+                                    for (sample in samples) decodedPeak = maxOf(decodedPeak, kotlin.math.abs(sample))
+                                    if (skipBuffer) continue
+
+                                    // 超轻模式：转换为单声道
+                                    val finalSamples = if (ultraLightMode && originalChannelCount > 1 && monoBuffer != null)
+                                        convertToMono(samples, originalChannelCount, monoBuffer)
+                                    else samples
+
+                                    // 降采样 TIPS：暂时禁用降采样
+                                    // val downsampledSamples = downsampler.process(finalSamples)
+
+                                    // 超轻模式：进一步减少样本量
+                                    val finalProcessedSamples = if (ultraLightMode && /*downsampledSamples*/finalSamples.isNotEmpty())
+                                        reduceSamples(/*downsampledSamples*/finalSamples, newChannelCount)
+                                    else /*downsampledSamples*/finalSamples
+
+                                    // 添加到响度计算器
+                                    if (finalProcessedSamples.isNotEmpty()) {
+                                        loudnessCalculator.addSamples(finalProcessedSamples)
+                                        totalSamplesProcessed += finalSamples.size / newChannelCount
                                     }
                                 }
-
-                                // 处理PCM数据
-                                val samples = processPcmData(outputBuffer, bufferInfo.size, pcmFormat, sampleBuffer)
-
-                                // 检查样本数据是否有效
-                                if (samples.isEmpty()) {
-                                    codec.releaseOutputBuffer(outputBufferIndex, false)
-                                    continue
-                                }
-
-                                // 超轻模式：转换为单声道
-                                val finalSamples = if (ultraLightMode && originalChannelCount > 1 && monoBuffer != null)
-                                    convertToMono(samples, originalChannelCount, monoBuffer)
-                                else samples
-
-                                // 降采样 TIPS：暂时禁用降采样
-                                // val downsampledSamples = downsampler.process(finalSamples)
-
-                                // 超轻模式：进一步减少样本量
-                                val finalProcessedSamples = if (ultraLightMode && /*downsampledSamples*/finalSamples.isNotEmpty())
-                                    reduceSamples(/*downsampledSamples*/finalSamples, newChannelCount)
-                                else /*downsampledSamples*/finalSamples
-
-                                // 添加到响度计算器
-                                if (finalProcessedSamples.isNotEmpty()) {
-                                    loudnessCalculator.addSamples(finalProcessedSamples)
-                                    totalSamplesProcessed += finalSamples.size / newChannelCount
-                                }
                             }
 
                             codec.releaseOutputBuffer(outputBufferIndex, false)
@@ -223,8 +232,28 @@ class LightweightLoudnessAnalyzer {
                             if (bufferInfo.flags and MediaCodec.BUFFER_FLAG_END_OF_STREAM != 0) isEnded = true
                         }
 
-                        outputBufferIndex == MediaCodec.INFO_OUTPUT_FORMAT_CHANGED ->
-                            Log.d(TAG, "Output format changed")
+                        outputBufferIndex == MediaCodec.INFO_OUTPUT_FORMAT_CHANGED -> {
+                            val outputFormat = codec.outputFormat
+                            pcmFormat = detectPcmFormat(outputFormat)
+
+                            val outputChannels = if (outputFormat.containsKey(MediaFormat.KEY_CHANNEL_COUNT))
+                                outputFormat.getInteger(MediaFormat.KEY_CHANNEL_COUNT) else originalChannelCount
+                            val outputSampleRate = if (outputFormat.containsKey(MediaFormat.KEY_SAMPLE_RATE))
+                                outputFormat.getInteger(MediaFormat.KEY_SAMPLE_RATE) else sampleRate
+
+                            // 声道数或采样率与轨道格式不同（如解码器自行下混或重采样）时按实际输出重新配置，之前的结果作废
+                            if (outputChannels != originalChannelCount || outputSampleRate != sampleRate) {
+                                originalChannelCount = outputChannels
+                                sampleRate = outputSampleRate
+                                newChannelCount = if (ultraLightMode) 1 else outputChannels
+                                maxSamples = (maxAnalysisDuration * sampleRate / 1_000_000L).toInt()
+                                loudnessCalculator.reconfigure(newChannelCount, sampleRate)
+                                totalSamplesProcessed = 0L
+                                decodedPeak = 0f
+                            }
+
+                            Log.d(TAG, "Output format changed: $pcmFormat, ${sampleRate}Hz, ${originalChannelCount}ch")
+                        }
 
                         outputBufferIndex == MediaCodec.INFO_TRY_AGAIN_LATER -> {} // 继续等待
 
@@ -247,14 +276,25 @@ class LightweightLoudnessAnalyzer {
             // TODO：要不要经典解方程？还有就是EBUR里面那个魔数他妈的？另外要不要移到EBUR里
             // loudness += if (ultraLightMode) 5.4f else 2.7f
 
+            // 吞吐量（百万样本/秒），含解码耗时
+            val elapsedSec = (System.nanoTime() - startTimeNs) / 1e9
+            val rate = if (elapsedSec > 0) (totalSamplesProcessed * newChannelCount / elapsedSec / 1e6).toFloat() else 0f
+
             Log.d(
                 TAG,
-                "Analysis complete: $loudness LUFS, processed $totalSamplesProcessed samples (ultra: $ultraLightMode)"
+                "Analysis complete: $loudness LUFS, processed $totalSamplesProcessed samples (ultra: $ultraLightMode), $rate MS/s"
+            )
+            val invalidReason = if (totalSamplesProcessed > 0) loudnessCalculator.getInvalidReason()
+            else InvalidReason.NO_SAMPLES
+
+            return AudioLoudnessInfo(
+                loudness, decodedPeak, invalidReason, loudnessCalculator.getStereoWidth(),
+                loudnessCalculator.isLowConfidence(), loudnessCalculator.getLoudnessRange(), rate,
+                loudnessCalculator.getTruePeak()
             )
-            return AudioLoudnessInfo(loudness)
         } catch (e: Exception) {
             Log.e(TAG, "Error analyzing file", e)
-            return AudioLoudnessInfo(-70f)
+            return AudioLoudnessInfo(-70f, invalidReason = InvalidReason.UNREADABLE)
         } finally {
             // 8. 清理资源
             try {
@@ -267,6 +307,48 @@ class LightweightLoudnessAnalyzer {
         }
     }
 
+    /**
+     * 只读取容器头检查格式，不解码样本，适合批量分析前先筛掉不支持的文件
+     * 无法识别或没有音轨时返回 null
+     */
+    fun probeFormat(audioFile: File): DetectedFormat? {
+        if (!audioFile.exists() || !audioFile.canRead()) {
+            Log.e(TAG, "File not accessible: ${audioFile.absolutePath}")
+            return null
+        }
+
+        val extractor = MediaExtractor()
+        try {
+            extractor.setDataSource(audioFile.absolutePath)
+
+            val audioTrackIndex = findAudioTrack(extractor)
+            if (audioTrackIndex < 0) return null
+
+            val format = extractor.getTrackFormat(audioTrackIndex)
+            val mime = format.getString(MediaFormat.KEY_MIME) ?: return null
+
+            val bitsPerSample = if (format.containsKey(MediaFormat.KEY_PCM_ENCODING) || mime == MediaFormat.MIMETYPE_AUDIO_RAW)
+                when (detectPcmFormat(format)) {
+                    PcmFormat.PCM_16BIT -> 16
+                    PcmFormat.PCM_24BIT -> 24
+                    PcmFormat.PCM_32BIT, PcmFormat.PCM_FLOAT -> 32
+                }
+            else null
+
+            val durationUs = if (format.containsKey(MediaFormat.KEY_DURATION)) format.getLong(MediaFormat.KEY_DURATION) else -1L
+
+            return DetectedFormat(
+                mime, format.getInteger(MediaFormat.KEY_SAMPLE_RATE), format.getInteger(MediaFormat.KEY_CHANNEL_COUNT),
+                bitsPerSample, durationUs
+            )
+        } catch (e: Exception) {
+            Log.e(TAG, "Error probing file", e)
+            return null
+        } finally {
+            extractor.release()
+        }
+    }
+
     /**
      * 超轻模式样本减少策略
      */
@@ -380,9 +462,9 @@ class LightweightLoudnessAnalyzer {
         }
     }
 
-    // 优化的PCM数据处理
+    // 优化的PCM数据处理，从第 startSample 个样本起最多读满 outputBuffer，只输出完整的帧，避免残缺的尾帧让后续声道错位
     private fun processPcmData(
-        buffer: ByteBuffer, size: Int, format: PcmFormat, outputBuffer: FloatArray
+        buffer: ByteBuffer, size: Int, startSample: Int, format: PcmFormat, channelCount: Int, outputBuffer: FloatArray
     ): FloatArray {
         val bytesPerSample = when (format) {
             PcmFormat.PCM_16BIT -> 2
@@ -390,18 +472,19 @@ class LightweightLoudnessAnalyzer {
             PcmFormat.PCM_32BIT, PcmFormat.PCM_FLOAT -> 4
         }
 
-        val sampleCount = size / bytesPerSample
-        if (sampleCount <= 0) return floatArrayOf()
+        val frameCount = minOf(size / bytesPerSample - startSample, outputBuffer.size) / channelCount
+        if (frameCount <= 0) return floatArrayOf()
 
-        val actualOutputSize = minOf(sampleCount, outputBuffer.size)
+        // 丢弃不足一帧的余量
+        val actualOutputSize = frameCount * channelCount
 
         // 直接在输出缓冲区中工作，避免额外分配
         when (format) {
             PcmFormat.PCM_16BIT -> for (i in 0 until actualOutputSize)
-                outputBuffer[i] = buffer.getShort(i * 2) / 32768f
+                outputBuffer[i] = buffer.getShort((startSample + i) * 2) / 32768f
 
             PcmFormat.PCM_24BIT -> for (i in 0 until actualOutputSize) {
-                val offset = i * 3
+                val offset = (startSample + i) * 3
                 val byte1 = buffer.get(offset).toInt() and 0xFF
                 val byte2 = buffer.get(offset + 1).toInt() and 0xFF
                 val byte3 = buffer.get(offset + 2).toInt()
@@ -410,10 +493,11 @@ class LightweightLoudnessAnalyzer {
             }
 
             PcmFormat.PCM_32BIT -> for (i in 0 until actualOutputSize)
-                outputBuffer[i] = buffer.getInt(i * 4) / 2.1474836E9f
+                outputBuffer[i] = buffer.getInt((startSample + i) * 4) / 2.1474836E9f
 
-            PcmFormat.PCM_FLOAT -> for (i in 0 until actualOutputSize)
-                outputBuffer[i] = buffer.getFloat(i * 4)
+            // 浮点数据直接按视图批量读取，省去逐个样本的转换
+            PcmFormat.PCM_FLOAT -> buffer.duplicate().order(buffer.order()).apply { position(0) }
+                .asFloatBuffer().apply { position(startSample) }.get(outputBuffer, 0, actualOutputSize)
         }
 
         return outputBuffer.copyOfRange(0, actualOutputSize)
@@ -0,0 +1,56 @@
+package me.earzuchan.dynactrl
+
+import me.earzuchan.dynactrl.models.AudioLoudnessInfo
+import kotlin.math.exp
+import kotlin.math.pow
+
+/**
+ * 流式自适应响度归一化：边测量边按当前的综合响度估计把输出推向目标响度
+ *
+ * 增益按 smoothingTime 的时间常数平滑变化，且不超过 maxGainDb；
+ * 输出是浮点且不做限幅，需要防削波时请在后面接限制器
+ */
+class StreamingNormalizer(
+    private val channels: Int,
+    sampleRate: Int,
+    private val targetLufs: Float,
+    smoothingTime: Float = DEFAULT_SMOOTHING_TIME,
+    private val maxGainDb: Float = AudioLoudnessInfo.DEFAULT_MAX_GAIN_DB
+) {
+    companion object {
+        const val DEFAULT_SMOOTHING_TIME = 2f // 2s增益平滑
+    }
+
+    private val analyzer = LightweightEbuR128(channels, sampleRate)
+    private val smoothingCoeff = exp(-1.0 / (smoothingTime * sampleRate)).toFloat()
+    private var gain = 1f
+
+    val currentGain: Float get() = gain
+
+    /**
+     * 处理交错样本，长度必须是声道数的整数倍，否则抛出 IllegalArgumentException
+     */
+    fun process(samples: FloatArray): FloatArray {
+        if (samples.isEmpty()) return floatArrayOf()
+        require(samples.size % channels == 0) { "Sample count ${samples.size} is not a multiple of $channels channels" }
+
+        analyzer.addSamples(samples)
+
+        // 尚不可测量时保持当前增益
+        val lufs = analyzer.getIntegratedLoudnessIncremental()
+        val targetGain = if (lufs.isFinite()) 10f.pow(minOf(targetLufs - lufs, maxGainDb) / 20f) else gain
+
+        val output = FloatArray(samples.size)
+        for (frame in 0 until samples.size / channels) {
+            gain = targetGain + (gain - targetGain) * smoothingCoeff
+            for (ch in 0 until channels) {
+                val idx = frame * channels + ch
+                output[idx] = samples[idx] * gain
+            }
+        }
+
+        return output
+    }
+
+    fun getMeasuredLoudness(): Float = analyzer.getIntegratedLoudness()
+}
@@ -6,30 +6,69 @@ import androidx.media3.common.C
 import androidx.media3.common.audio.AudioProcessor
 import androidx.media3.common.audio.BaseAudioProcessor
 import androidx.media3.common.util.UnstableApi
+import me.earzuchan.dynactrl.LightweightEbuR128
 import me.earzuchan.dynactrl.models.AudioLoudnessInfo
+import me.earzuchan.dynactrl.utils.LookaheadLimiter
+import me.earzuchan.dynactrl.utils.softKneeGain
 import java.nio.ByteBuffer
 import kotlin.math.pow
 
+/**
+ * 响度归一化 + 限制器
+ *
+ * @param lookaheadTime 前视限制器的前视时长（秒），如 0.005f；为 0 时使用即时限制器（无额外延迟）
+ * @param measureOutput 是否同时测量处理后输出的响度，供界面轮询 [getLiveLoudness]
+ * @param peakCeilingDb 峰值天花板（dB），增益不会把峰值推过它；设为 null 则不按峰值限制增益，只靠限制器兜底
+ * @param targetLufs 归一化目标响度，如 -14f（流媒体）、-16f（播客）、-23f（EBU 广播）；高于目标的音轨会被衰减
+ * @param limiterThreshold 限制器阈值（线性满幅比例）
+ * @param limiterAttackTime 限制器攻击时间（秒），仅即时限制器使用；前视限制器在前视时间内压到位
+ * @param limiterReleaseTime 限制器释放时间（秒）
+ * @param limiterKneeDb 软拐点宽度（dB），默认 0 即硬拐点（与之前的限制器行为一致）；增大后阈值以下的峰值也会被轻微压缩
+ */
 @OptIn(UnstableApi::class)
-class DynamicsProcessor : BaseAudioProcessor() {
+class DynamicsProcessor(
+    private val lookaheadTime: Float = 0f,
+    private val measureOutput: Boolean = false,
+    private val peakCeilingDb: Float? = AudioLoudnessInfo.DEFAULT_PEAK_CEILING_DB,
+    val targetLufs: Float = DEFAULT_TARGET_LUFS,
+    limiterThreshold: Float = DEFAULT_LIMITER_THRESHOLD,
+    private val limiterAttackTime: Float = DEFAULT_LIMITER_ATTACK_TIME,
+    private val limiterReleaseTime: Float = DEFAULT_LIMITER_RELEASE_TIME,
+    limiterKneeDb: Float = DEFAULT_LIMITER_KNEE_DB
+) : BaseAudioProcessor() {
     companion object {
         private const val TAG = "DynamicsProcessor"
-        private const val TARGET_LUFS = -14f // Spotify标准
-        private const val LIMITER_THRESHOLD = 0.95f // 限制器阈值
-        private const val LIMITER_ATTACK_TIME = 0.001f // 1ms攻击时间
-        private const val LIMITER_RELEASE_TIME = 0.01f // 10ms释放时间
+        const val DEFAULT_TARGET_LUFS = -14f // Spotify标准
+        const val DEFAULT_LIMITER_THRESHOLD = 0.95f // 限制器阈值
+        const val DEFAULT_LIMITER_ATTACK_TIME = 0.001f // 1ms攻击时间
+        const val DEFAULT_LIMITER_RELEASE_TIME = 0.01f // 10ms释放时间
+        const val DEFAULT_LIMITER_KNEE_DB = 0f // 硬拐点
+        const val DEFAULT_LOOKAHEAD_TIME = 0.005f // 5ms前视
+        private const val INT32_SCALE = 2147483648.0 // 32位整数满幅
     }
 
     private var currentLoudnessInfo: AudioLoudnessInfo? = null
-    private var gainScale = 1.0f
+    private var gainScale = 1.0 // 增益和限制器都以双精度计算，只在写出时收窄，避免峰值因舍入越过阈值
     private var sampleRate = 44100
     private var channelCount = 2
     private var bytesPerSample = 2 // 16-bit
+    private var encoding = C.ENCODING_PCM_16BIT // 32 位时据此区分整数和浮点
+    private var peakLimited = false
+
+    private val limiterThreshold = limiterThreshold.toDouble()
+    private val limiterKneeDb = limiterKneeDb.toDouble()
 
     // 限制器状态
-    private var limiterGainReduction = 1.0f
-    private var attackCoeff = 0.0f
-    private var releaseCoeff = 0.0f
+    private var limiterGainReduction = 1.0
+    private var attackCoeff = 0.0
+    private var releaseCoeff = 0.0
+
+    // 前视限制器（lookaheadTime > 0 时启用）
+    private var lookaheadLimiter: LookaheadLimiter? = null
+    private var lookaheadFrame = DoubleArray(0)
+
+    // 输出响度测量（measureOutput 时启用）
+    private var liveAnalyzer: LightweightEbuR128? = null
 
     // 设置当前音轨的响度信息，以计算处理（增益和限制）参数
     fun setCurrentTrackLoudness(loudnessInfo: AudioLoudnessInfo) {
@@ -42,15 +81,28 @@ class DynamicsProcessor : BaseAudioProcessor() {
         val loudnessInfo = currentLoudnessInfo ?: return
         val lufs = loudnessInfo.lufs
 
-        gainScale = (if (lufs.isNaN() || lufs.isInfinite() || lufs < -70f) 1.0f
-        else 10.0.pow((TARGET_LUFS - lufs) / 20.0).toFloat())
+        // 不可测量（含 -70 LUFS 及以下的静音）时不调整
+        val loudnessGain = (if (lufs.isNaN() || lufs.isInfinite() || lufs <= -70f) 1.0
+        else 10.0.pow((targetLufs - lufs) / 20.0))
 
-        Log.d(TAG, "Track LUFS: $lufs, Calculated gain scale: $gainScale")
+        // 按峰值（优先真峰值）限制增益，避免归一化把峰值推过天花板
+        val ceiling = peakCeilingDb
+        val peakGain = if (ceiling == null) null else loudnessInfo.peakDb()?.let { 10.0.pow((ceiling - it) / 20.0) }
+        peakLimited = peakGain != null && peakGain < loudnessGain
+        gainScale = if (peakGain != null && peakLimited) peakGain else loudnessGain
+
+        Log.d(TAG, "Track LUFS: $lufs, Calculated gain scale: $gainScale, peak limited: $peakLimited")
     }
 
+    /**
+     * 当前增益是否被峰值天花板限制（而不是由目标响度决定）
+     */
+    fun isPeakLimited(): Boolean = peakLimited
+
     override fun onConfigure(inputAudioFormat: AudioProcessor.AudioFormat): AudioProcessor.AudioFormat {
         sampleRate = inputAudioFormat.sampleRate
         channelCount = inputAudioFormat.channelCount
+        encoding = inputAudioFormat.encoding
         bytesPerSample = when (inputAudioFormat.encoding) {
             C.ENCODING_PCM_16BIT -> 2
             C.ENCODING_PCM_24BIT -> 3
@@ -60,8 +112,15 @@ class DynamicsProcessor : BaseAudioProcessor() {
         }
 
         // 计算限制器系数
-        attackCoeff = kotlin.math.exp(-1.0 / (LIMITER_ATTACK_TIME * sampleRate)).toFloat()
-        releaseCoeff = kotlin.math.exp(-1.0 / (LIMITER_RELEASE_TIME * sampleRate)).toFloat()
+        attackCoeff = kotlin.math.exp(-1.0 / (limiterAttackTime * sampleRate))
+        releaseCoeff = kotlin.math.exp(-1.0 / (limiterReleaseTime * sampleRate))
+
+        lookaheadLimiter = if (lookaheadTime > 0f) LookaheadLimiter(
+            channelCount, (lookaheadTime * sampleRate).toInt(), limiterThreshold, releaseCoeff, limiterKneeDb
+        ) else null
+        lookaheadFrame = DoubleArray(channelCount)
+
+        liveAnalyzer = if (measureOutput) LightweightEbuR128(channelCount, sampleRate) else null
 
         Log.d(
             TAG,
@@ -77,16 +136,88 @@ class DynamicsProcessor : BaseAudioProcessor() {
         val inputSize = inputBuffer.remaining()
         val outputBuffer = replaceOutputBuffer(inputSize)
 
+        val limiter = lookaheadLimiter
         when (bytesPerSample) {
-            2 -> processInt16(inputBuffer, outputBuffer)
+            2 -> if (limiter != null) processLookahead(inputBuffer, outputBuffer, limiter)
+            else processInt16(inputBuffer, outputBuffer)
 
-            4 -> processFloat32(inputBuffer, outputBuffer)
+            4 -> if (limiter != null) processLookahead(inputBuffer, outputBuffer, limiter)
+            else if (encoding == C.ENCODING_PCM_32BIT) processInt32(inputBuffer, outputBuffer)
+            else processFloat32(inputBuffer, outputBuffer)
 
             // 对于其他格式，直接复制（不处理） TODO：或有问题
             else -> outputBuffer.put(inputBuffer)
         }
 
         outputBuffer.flip()
+        measure(outputBuffer)
+    }
+
+    // 把处理后的输出送入测量
+    private fun measure(outputBuffer: ByteBuffer) {
+        val analyzer = liveAnalyzer ?: return
+        if (bytesPerSample != 2 && bytesPerSample != 4) return
+
+        val view = outputBuffer.duplicate().order(outputBuffer.order())
+        analyzer.addSamples(FloatArray(view.remaining() / bytesPerSample) { readSample(view).toFloat() })
+    }
+
+    /**
+     * 处理后输出的当前综合响度（LUFS），未启用 measureOutput 或尚不可测量时为 -INF
+     * 在音频线程更新，其他线程读到的是近似的最新值
+     */
+    fun getLiveLoudness(): Float = liveAnalyzer?.getIntegratedLoudnessIncremental() ?: Float.NEGATIVE_INFINITY
+
+    // 前视限制器处理（按帧），16/32位整数和32位浮点共用
+    private fun processLookahead(inputBuffer: ByteBuffer, outputBuffer: ByteBuffer, limiter: LookaheadLimiter) {
+        val framesCount = inputBuffer.remaining() / (bytesPerSample * channelCount)
+
+        repeat(framesCount) {
+            for (ch in 0 until channelCount) lookaheadFrame[ch] = readSample(inputBuffer) * gainScale
+            limiter.process(lookaheadFrame)
+            for (ch in 0 until channelCount) writeSample(outputBuffer, lookaheadFrame[ch])
+        }
+    }
+
+    private fun readSample(buffer: ByteBuffer): Double = when {
+        bytesPerSample == 2 -> buffer.short.toDouble() / Short.MAX_VALUE
+        encoding == C.ENCODING_PCM_32BIT -> buffer.int / INT32_SCALE
+        else -> buffer.float.toDouble()
+    }
+
+    private fun writeSample(buffer: ByteBuffer, sample: Double) {
+        when {
+            bytesPerSample == 2 -> buffer.putShort(
+                (sample * Short.MAX_VALUE).toInt().coerceIn(Short.MIN_VALUE.toInt(), Short.MAX_VALUE.toInt()).toShort()
+            )
+
+            encoding == C.ENCODING_PCM_32BIT -> buffer.putInt(toInt32(sample))
+            else -> buffer.putFloat(sample.toFloat())
+        }
+    }
+
+    // 满幅 1.0 对应 2^31，超出部分截到 Int 范围
+    private fun toInt32(sample: Double): Int =
+        (sample * INT32_SCALE).toLong().coerceIn(Int.MIN_VALUE.toLong(), Int.MAX_VALUE.toLong()).toInt()
+
+    /**
+     * 前视限制器引入的延迟（帧），输出比输入晚这么多；即时限制器时为 0
+     */
+    fun getLatencyFrames(): Int = lookaheadLimiter?.latencyFrames ?: 0
+
+    // 输入结束时把延迟线里剩余的样本排出去，同样计入输出响度测量
+    override fun onQueueEndOfStream() {
+        val limiter = lookaheadLimiter ?: return
+        if (limiter.latencyFrames == 0 || (bytesPerSample != 2 && bytesPerSample != 4)) return
+
+        val outputBuffer = replaceOutputBuffer(limiter.latencyFrames * channelCount * bytesPerSample)
+        repeat(limiter.latencyFrames) {
+            lookaheadFrame.fill(0.0)
+            limiter.process(lookaheadFrame)
+            for (ch in 0 until channelCount) writeSample(outputBuffer, lookaheadFrame[ch])
+        }
+        outputBuffer.flip()
+        measure(outputBuffer)
     }
 
     // 处理16位整数PCM数据
@@ -96,7 +227,7 @@ class DynamicsProcessor : BaseAudioProcessor() {
         // CHECK：好像有超采样？
         repeat(samplesCount) {
             // 读取16位样本
-            val sample = inputBuffer.short.toFloat() / Short.MAX_VALUE
+            val sample = inputBuffer.short.toDouble() / Short.MAX_VALUE
 
             // 应用增益
             var processedSample = sample * gainScale
@@ -112,13 +243,26 @@ class DynamicsProcessor : BaseAudioProcessor() {
         }
     }
 
+    // 处理32位整数PCM数据
+    private fun processInt32(inputBuffer: ByteBuffer, outputBuffer: ByteBuffer) {
+        val samplesCount = inputBuffer.remaining() / 4
+
+        repeat(samplesCount) {
+            // 读取32位整数样本
+            val sample = inputBuffer.int / INT32_SCALE
+
+            // 应用增益和限制器，转换回32位并写入输出
+            outputBuffer.putInt(toInt32(applyLimiter(sample * gainScale)))
+        }
+    }
+
     // 处理32位浮点PCM数据
     private fun processFloat32(inputBuffer: ByteBuffer, outputBuffer: ByteBuffer) {
         val samplesCount = inputBuffer.remaining() / 4
 
         repeat(samplesCount) {
             // 读取浮点样本
-            var sample = inputBuffer.float
+            var sample = inputBuffer.float.toDouble()
 
             // 应用增益
             sample *= gainScale
@@ -126,39 +270,42 @@ class DynamicsProcessor : BaseAudioProcessor() {
             // 应用限制器
             sample = applyLimiter(sample)
 
-            outputBuffer.putFloat(sample)
+            outputBuffer.putFloat(sample.toFloat())
         }
     }
 
     // 应用限制器处理
-    private fun applyLimiter(sample: Float): Float {
-        val absSample = kotlin.math.abs(sample)
-
-        if (absSample > LIMITER_THRESHOLD) {
-            // 计算需要的增益衰减
-            val targetGain = LIMITER_THRESHOLD / absSample
+    private fun applyLimiter(sample: Double): Double {
+        // 计算需要的增益衰减（软拐点）
+        val targetGain = softKneeGain(kotlin.math.abs(sample), limiterThreshold, limiterKneeDb)
 
+        if (targetGain < 1.0) {
             // 平滑增益变化
             limiterGainReduction =
                 if (targetGain < limiterGainReduction) targetGain + (limiterGainReduction - targetGain) * attackCoeff
                 else targetGain + (limiterGainReduction - targetGain) * releaseCoeff
-        } else limiterGainReduction = 1.0f + (limiterGainReduction - 1.0f) * releaseCoeff // 释放增益衰减
+        } else limiterGainReduction = 1.0 + (limiterGainReduction - 1.0) * releaseCoeff // 释放增益衰减
 
         return sample * limiterGainReduction
     }
 
-    override fun isActive(): Boolean = currentLoudnessInfo != null && gainScale != 1.0f
+    override fun isActive(): Boolean = (currentLoudnessInfo != null && gainScale != 1.0) || measureOutput
 
     override fun onFlush() {
         // 重置限制器状态
-        limiterGainReduction = 1.0f
+        limiterGainReduction = 1.0
+        lookaheadLimiter?.reset()
+        liveAnalyzer?.flushFilterState()
     }
 
     override fun onReset() {
         // 重置所有状态
         currentLoudnessInfo = null
-        gainScale = 1.0f
+        gainScale = 1.0
+        peakLimited = false
 
-        limiterGainReduction = 1.0f
+        limiterGainReduction = 1.0
+        lookaheadLimiter = null
+        liveAnalyzer = null
     }
 }
\ No newline at end of file
@@ -0,0 +1,11 @@
+package me.earzuchan.dynactrl.models
+
+/**
+ * 专辑模式增益（所有音轨共用同一个增益，保留曲间动态）
+ */
+data class AlbumGain(
+    val albumLufs: Float, // 专辑整体响度（各轨能量平均）
+    val gainDb: Float, // 达到目标响度所需的增益
+    val peakSafeGainDb: Float, // 按最大峰值（优先真峰值）限制后不超过峰值天花板的增益
+    val clippingTracks: List<Int>, // 直接应用 gainDb 会超过峰值天花板的音轨下标
+)
@@ -1,5 +1,121 @@
 package me.earzuchan.dynactrl.models
 
+import kotlin.math.log10
+import kotlin.math.pow
+import kotlin.math.roundToInt
+
 data class AudioLoudnessInfo(
     val lufs: Float, // Integrated loudness in LUFS
-)
\ No newline at end of file
+    val samplePeak: Float = 0f, // Linear sample peak, 1.0 = full scale
+    val invalidReason: InvalidReason? = null, // Why lufs couldn't be measured, null if valid
+    val stereoWidthDb: Float? = null, // Side/mid energy ratio in dB, null unless measured as stereo
+    val lowConfidence: Boolean = false, // Integrated value based on under ~1 s of gated audio
+    val lra: Float? = null, // Loudness range in LU, null if unmeasurable
+    val analysisRateMegaSamplesPerSec: Float = 0f, // Analysis throughput including decoding, 0 if unknown
+    val truePeak: Float? = null, // Linear 4x-oversampled true peak, null if not measured
+) {
+    companion object {
+        const val REPLAYGAIN_REFERENCE_LUFS = -18f // ReplayGain 2.0 参考响度
+        const val DEFAULT_MAX_GAIN_DB = 20f // 超过此增益会明显放大底噪
+        const val UNMEASURABLE_MILLIBEL = Int.MIN_VALUE // 无法测量时的毫贝哨兵值
+        const val DEFAULT_PEAK_CEILING_DB = -1f // EBU R128 推荐的 -1 dBTP
+
+        /**
+         * 计算专辑模式增益（类似 ReplayGain 专辑模式），无法测量的音轨不参与平均
+         * 峰值优先用真峰值（见 [peakDb]），超过 peakCeilingDb 的音轨记为会削波
+         */
+        fun albumGain(
+            trackInfos: List<AudioLoudnessInfo>, targetLufs: Float, peakCeilingDb: Float = DEFAULT_PEAK_CEILING_DB
+        ): AlbumGain {
+            val measured = trackInfos.filter { it.isMeasurable() }
+            if (measured.isEmpty()) return AlbumGain(Float.NEGATIVE_INFINITY, 0f, 0f, emptyList())
+
+            // 在能量域平均
+            val meanEnergy = measured.map { 10.0.pow(it.lufs / 10.0) }.average()
+            val albumLufs = (10.0 * log10(meanEnergy)).toFloat()
+            val gainDb = targetLufs - albumLufs
+
+            val maxPeakDb = trackInfos.mapNotNull { it.peakDb() }.maxOrNull()
+            val peakSafeGainDb = if (maxPeakDb != null) minOf(gainDb, peakCeilingDb - maxPeakDb) else gainDb
+            val clippingTracks = trackInfos.indices.filter { i ->
+                trackInfos[i].peakDb()?.let { it + gainDb > peakCeilingDb } == true
+            }
+
+            return AlbumGain(albumLufs, gainDb, peakSafeGainDb, clippingTracks)
+        }
+    }
+
+    /**
+     * 归一化到目标响度所需增益是否超过安全上限，用于提示用户而不是盲目提升
+     * 无法测量的结果不会被归一化，因此总是 false
+     */
+    fun isExcessiveGain(targetLufs: Float, maxGainDb: Float = DEFAULT_MAX_GAIN_DB): Boolean =
+        isMeasurable() && targetLufs - lufs > maxGainDb
+
+    /**
+     * 响度是否可用：分析成功（无 [invalidReason]）且为高于 -70 LUFS 的有限值
+     * 分析失败时 lufs 是 -70 的占位值，不能当作测量结果使用
+     */
+    fun isMeasurable(): Boolean = invalidReason == null && lufs.isFinite() && lufs > -70f
+
+    /**
+     * 以百分之一 LU 为单位的整数响度（如 -23.45 LUFS -> -2345），便于紧凑存储
+     * 无法测量（见 [isMeasurable]）时返回 [UNMEASURABLE_MILLIBEL]
+     */
+    fun lufsMillibel(): Int = if (!isMeasurable()) UNMEASURABLE_MILLIBEL else (lufs * 100f).roundToInt()
+
+    /**
+     * 真峰值（dBTP），未测量时为 null
+     */
+    fun truePeakDbtp(): Float? = truePeak?.let { 20f * log10(it) }
+
+    /**
+     * 真峰值是否超过上限（dBTP，默认 EBU R128 的 -1 dBTP），用于交付前检查
+     * 未测量真峰值时为 false，不退回采样峰值（采样峰值会低估样本间峰值）
+     */
+    fun exceedsTruePeakLimit(limitDbtp: Float = DEFAULT_PEAK_CEILING_DB): Boolean =
+        truePeakDbtp()?.let { it > limitDbtp } ?: false
+
+    /**
+     * 峰值（dB）：有真峰值时用真峰值（dBTP），否则退回采样峰值（dBFS），无信号时为 null
+     */
+    fun peakDb(): Float? = truePeakDbtp() ?: if (samplePeak > 0f) 20f * log10(samplePeak) else null
+
+    /**
+     * 到峰值上限的余量（dB），正值表示还能提升多少，负值表示已超出
+     * 未测量真峰值时基于采样峰值，真实余量可能更小
+     */
+    fun peakHeadroomDb(ceilingDb: Float): Float = peakDb()?.let { ceilingDb - it } ?: Float.POSITIVE_INFINITY
+
+    /**
+     * 动态评分 0~100，越低说明压缩越狠（“响度战争”式母带）
+     *
+     * score = 50 * clamp(LRA / 20 LU) + 50 * clamp((PLR - 6 dB) / 14 dB)
+     * 其中 PLR（峰值响度比）= 峰值（优先真峰值）- 综合响度。综合响度就是 K-weighting 后门控的 RMS 电平，
+     * 所以 PLR 即按 BS.1770 加权的波峰因数（峰值 / RMS，dB），同时涵盖波峰因数和真峰值响度比，
+     * 不再另加一项未加权的波峰因数，以免同一特征重复计分
+     * LRA >= 20 LU、PLR >= 20 dB 记满分，PLR <= 6 dB（砖墙限幅）该项为 0
+     * 响度、LRA 或峰值不可用时为 null
+     */
+    fun dynamicsScore(): Float? {
+        val range = lra ?: return null
+        val peak = peakDb() ?: return null
+        if (!lufs.isFinite() || lufs < -70f) return null
+
+        val plr = peak - lufs
+        val lraPart = (range / 20f).coerceIn(0f, 1f)
+        val plrPart = ((plr - 6f) / 14f).coerceIn(0f, 1f)
+
+        return 50f * lraPart + 50f * plrPart
+    }
+
+    /**
+     * ReplayGain 2.0 音轨增益（dB），无法测量时为 null（不写标签）
+     */
+    fun replayGainTrackGain(): Float? = if (isMeasurable()) REPLAYGAIN_REFERENCE_LUFS - lufs else null
+
+    /**
+     * ReplayGain 2.0 音轨峰值（线性值）
+     */
+    fun replayGainTrackPeak(): Float = samplePeak
+}
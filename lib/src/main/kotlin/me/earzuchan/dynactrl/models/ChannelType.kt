@@ -0,0 +1,32 @@
+package me.earzuchan.dynactrl.models
+
+/**
+ * 声道类型及其 BS.1770 能量权重
+ */
+enum class ChannelType(val weight: Float) {
+    LEFT(1f),
+    RIGHT(1f),
+    CENTER(1f),
+    LFE(0f), // 低频效果声道不计入响度
+    LEFT_SURROUND(1.41f),
+    RIGHT_SURROUND(1.41f),
+    LEFT_SIDE(1.41f),
+    RIGHT_SIDE(1.41f),
+    UNUSED(0f);
+
+    companion object {
+        /**
+         * 按声道数给出常见布局（WAV / Android 的默认顺序），没有对应布局时返回 null（全部按权重 1 计算）
+         */
+        fun defaultLayout(channels: Int): List<ChannelType>? = when (channels) {
+            1 -> listOf(CENTER)
+            2 -> listOf(LEFT, RIGHT)
+            3 -> listOf(LEFT, RIGHT, CENTER)
+            4 -> listOf(LEFT, RIGHT, LEFT_SURROUND, RIGHT_SURROUND)
+            5 -> listOf(LEFT, RIGHT, CENTER, LEFT_SURROUND, RIGHT_SURROUND)
+            6 -> listOf(LEFT, RIGHT, CENTER, LFE, LEFT_SURROUND, RIGHT_SURROUND)
+            8 -> listOf(LEFT, RIGHT, CENTER, LFE, LEFT_SURROUND, RIGHT_SURROUND, LEFT_SIDE, RIGHT_SIDE)
+            else -> null
+        }
+    }
+}
@@ -0,0 +1,12 @@
+package me.earzuchan.dynactrl.models
+
+/**
+ * 只读文件头得到的音轨格式
+ */
+data class DetectedFormat(
+    val mime: String, // 如 audio/mpeg、audio/raw
+    val sampleRate: Int,
+    val channelCount: Int,
+    val bitsPerSample: Int?, // 仅未压缩 PCM 轨道可知
+    val durationUs: Long, // 未知时为 -1
+)
@@ -0,0 +1,9 @@
+package me.earzuchan.dynactrl.models
+
+/**
+ * 单个 400ms 门控块
+ */
+data class GatingBlock(
+    val startSeconds: Float, // 块起始时间（相对于第一个输入样本）
+    val energy: Float, // K-weighting 后的均方能量
+)
@@ -0,0 +1,11 @@
+package me.earzuchan.dynactrl.models
+
+/**
+ * 响度无法测量的原因
+ */
+enum class InvalidReason {
+    UNREADABLE, // 文件无法打开、没有音轨或解码失败
+    NO_SAMPLES, // 没有处理到任何样本
+    TOO_SHORT, // 不足以组成一个 400ms 门控块
+    ALL_BELOW_GATE // 门控块基本都在绝对门限（-70 LUFS）以下，如静音
+}
@@ -0,0 +1,12 @@
+package me.earzuchan.dynactrl.models
+
+/**
+ * 响度计算器某一时刻的结果快照，只含块能量和计算结果，不含采样缓冲区
+ */
+data class LoudnessSnapshot(
+    val blockEnergies: List<Float>, // 通过绝对门限的 400ms 块能量
+    val integratedLoudness: Float, // Integrated loudness in LUFS
+    val samplePeak: Float, // Linear sample peak
+    val lra: Float? = null, // 响度范围（LU），没有可用短期块时为 null
+    val truePeak: Float? = null, // 线性真峰值，未启用真峰值测量时为 null
+)
@@ -0,0 +1,15 @@
+package me.earzuchan.dynactrl.models
+
+/**
+ * 一次输入样本期间新完成的块数，消费方据此判断是否有新的测量值可读，而不必反复轮询
+ */
+data class SamplesAdded(
+    val integratedBlocks: Int, // 400ms 门控块（含未通过绝对门限的）
+    val shortTermBlocks: Int // 3s 短期块
+) {
+    val hasNewBlocks: Boolean get() = integratedBlocks > 0 || shortTermBlocks > 0
+
+    companion object {
+        val NONE = SamplesAdded(0, 0)
+    }
+}
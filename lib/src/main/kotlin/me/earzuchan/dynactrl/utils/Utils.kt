@@ -2,8 +2,10 @@ package me.earzuchan.dynactrl.utils
 
 import java.util.*
 import kotlin.math.PI
+import kotlin.math.abs
 import kotlin.math.cos
 import kotlin.math.exp
+import kotlin.math.log10
 import kotlin.math.pow
 import kotlin.math.sin
 import kotlin.math.sqrt
@@ -18,9 +20,10 @@ class CircularBuffer(private val capacity: Int) {
     private var currentSize = 0
 
     val size: Int get() = currentSize
+    val freeSpace: Int get() = capacity - currentSize
 
-    fun addAll(samples: FloatArray) {
-        for (sample in samples) add(sample)
+    fun addAll(samples: FloatArray, fromIndex: Int = 0, toIndex: Int = samples.size) {
+        for (i in fromIndex until toIndex) add(samples[i])
     }
 
     fun add(sample: Float) {
@@ -36,6 +39,12 @@ class CircularBuffer(private val capacity: Int) {
         }
     }
 
+    fun clear() {
+        head = 0
+        tail = 0
+        currentSize = 0
+    }
+
     fun removeFirst(count: Int) {
         val actualCount = minOf(count, currentSize)
         head = (head + actualCount) % capacity
@@ -108,6 +117,12 @@ class CompleteKWeighting(sampleRate: Int, channels: Int) {
         val afterHighpass = highpass.process(input)
         return shelf.process(afterHighpass)
     }
+
+    // 清空滤波器历史
+    fun reset() {
+        highpass.reset()
+        shelf.reset()
+    }
 }
 
 /**
@@ -135,6 +150,8 @@ class LowpassFilter(
 
         return output
     }
+
+    fun reset() = prevOutput.fill(0f)
 }
 
 /**
@@ -163,6 +180,11 @@ class HighpassFilter(sampleRate: Int, private val channels: Int, freq: Float = 3
 
         return output
     }
+
+    fun reset() {
+        prevOutput.fill(0f)
+        prevInput.fill(0f)
+    }
 }
 
 /**
@@ -226,4 +248,231 @@ class HighFreqShelf(
 
         return output
     }
-}
\ No newline at end of file
+
+    fun reset() {
+        x1.fill(0f)
+        x2.fill(0f)
+        y1.fill(0f)
+        y2.fill(0f)
+    }
+}
+
+/**
+ * 软拐点限制器的增益曲线：电平低于 threshold - kneeDb/2 时不压缩，拐点内按二次曲线过渡，
+ * 之上按无穷压缩比压到 threshold；输出电平随输入单调增加且始终不超过 threshold
+ * kneeDb 为 0 时即硬拐点（threshold / peak）
+ */
+fun softKneeGain(peak: Double, threshold: Double, kneeDb: Double): Double {
+    if (peak <= 0.0) return 1.0
+    if (kneeDb <= 0.0) return if (peak > threshold) threshold / peak else 1.0
+
+    val overDb = 20.0 * log10(peak / threshold)
+    val reductionDb = when {
+        overDb <= -kneeDb / 2 -> return 1.0
+        overDb < kneeDb / 2 -> (overDb + kneeDb / 2).pow(2) / (2 * kneeDb)
+        else -> overDb
+    }
+
+    return 10.0.pow(-reductionDb / 20.0)
+}
+
+/**
+ * 前视限制器（多声道联动）
+ *
+ * 信号延迟 lookaheadFrames - 1 帧，增益在峰值到来前就已平滑压到位，
+ * 因此输出不会超过阈值，瞬态被圆滑而不是被削顶；kneeDb > 0 时按 [softKneeGain] 在阈值下方就开始渐进压缩
+ */
+class LookaheadLimiter(
+    private val channels: Int,
+    lookaheadFrames: Int,
+    private val threshold: Double,
+    private val releaseCoeff: Double,
+    private val kneeDb: Double = 0.0
+) {
+    private val windowSize = lookaheadFrames.coerceAtLeast(1)
+    val latencyFrames = windowSize - 1
+
+    // 延迟线
+    private val delayLine = DoubleArray(latencyFrames * channels)
+    private var delayIndex = 0
+
+    // 窗口内所需增益的最小值（单调队列）
+    private val minValues = DoubleArray(windowSize)
+    private val minFrames = LongArray(windowSize)
+    private var minHead = 0
+    private var minCount = 0
+    private var frameCounter = 0L
+
+    // 对最小值再做一次窗口平均，使增益平滑过渡
+    private val smoothHistory = DoubleArray(windowSize) { 1.0 }
+    private var smoothSum = windowSize.toDouble()
+    private var smoothIndex = 0
+
+    private var gain = 1.0
+
+    /**
+     * 原地处理一帧（长度为 channels），峰值检测和增益运算都用双精度
+     */
+    fun process(frame: DoubleArray) {
+        var peak = 0.0
+        for (ch in 0 until channels) peak = maxOf(peak, abs(frame[ch]))
+        val required = softKneeGain(peak, threshold, kneeDb)
+
+        val held = pushMin(required)
+
+        smoothSum += held - smoothHistory[smoothIndex]
+        smoothHistory[smoothIndex] = held
+        smoothIndex = (smoothIndex + 1) % windowSize
+        val smoothed = smoothSum / windowSize
+
+        // 压低时立即跟随，回升时按释放系数平滑
+        gain = minOf(smoothed, releaseCoeff * gain + (1.0 - releaseCoeff) * smoothed)
+
+        if (latencyFrames > 0) {
+            for (ch in 0 until channels) {
+                val idx = delayIndex * channels + ch
+                val delayed = delayLine[idx]
+                delayLine[idx] = frame[ch]
+                frame[ch] = delayed * gain
+            }
+            delayIndex = (delayIndex + 1) % latencyFrames
+        } else for (ch in 0 until channels) frame[ch] *= gain
+
+        frameCounter++
+    }
+
+    private fun pushMin(value: Double): Double {
+        // 移除已滑出窗口的队首
+        while (minCount > 0 && minFrames[minHead] <= frameCounter - windowSize) {
+            minHead = (minHead + 1) % windowSize
+            minCount--
+        }
+
+        // 移除队尾不小于新值的元素
+        while (minCount > 0 && minValues[(minHead + minCount - 1) % windowSize] >= value) minCount--
+
+        val tail = (minHead + minCount) % windowSize
+        minValues[tail] = value
+        minFrames[tail] = frameCounter
+        minCount++
+
+        return minValues[minHead]
+    }
+
+    fun reset() {
+        delayLine.fill(0.0)
+        delayIndex = 0
+        minHead = 0
+        minCount = 0
+        frameCounter = 0L
+        smoothHistory.fill(1.0)
+        smoothSum = windowSize.toDouble()
+        smoothIndex = 0
+        gain = 1.0
+    }
+}
+
+/**
+ * 真峰值测量：4 倍过采样多相 FIR，取过采样后的最大绝对值
+ *
+ * @param taps 每相抽头数，默认 12 即 BS.1770 附录 2 的滤波器，其他长度用 Hann 窗 sinc 生成；
+ * 计算量与抽头数成正比，抽头越多越接近奈奎斯特的峰值越准（0.45·fs 的正弦：12 抽头约低 0.4 dB，48 抽头误差 < 0.01 dB）
+ */
+class TruePeakMeter(private val channels: Int, private val taps: Int = DEFAULT_TAPS) {
+    companion object {
+        const val DEFAULT_TAPS = 12
+
+        private val PHASE_0 = floatArrayOf(
+            0.0017089844f, 0.0109863281f, -0.0196533203f, 0.0332031250f, -0.0594482422f, 0.1373291016f,
+            0.9721679688f, -0.1022949219f, 0.0476074219f, -0.0266113281f, 0.0148925781f, -0.0083007813f
+        )
+        private val PHASE_1 = floatArrayOf(
+            -0.0291748047f, 0.0292968750f, -0.0517578125f, 0.0891113281f, -0.1665039063f, 0.4650878906f,
+            0.7797851563f, -0.2003173828f, 0.1015625000f, -0.0582275391f, 0.0330810547f, -0.0189208984f
+        )
+        private val BS1770_PHASES = arrayOf(PHASE_0, PHASE_1, PHASE_1.reversedArray(), PHASE_0.reversedArray())
+
+        // 截止在原采样率奈奎斯特频率的 sinc 低通，按相拆分，每相归一化为单位直流增益
+        private fun windowedSincPhases(taps: Int): Array<FloatArray> {
+            val length = taps * 4
+            val center = (length - 1) / 2.0
+            val h = DoubleArray(length) { i ->
+                val x = (i - center) / 4.0
+                val sinc = if (x == 0.0) 1.0 else sin(PI * x) / (PI * x)
+                sinc * (0.5 - 0.5 * cos(2.0 * PI * (i + 1) / (length + 1)))
+            }
+
+            return Array(4) { phase ->
+                val coeffs = DoubleArray(taps) { h[it * 4 + phase] }
+                val sum = coeffs.sum()
+                FloatArray(taps) { (coeffs[it] / sum).toFloat() }
+            }
+        }
+    }
+
+    init {
+        require(taps > 0) { "Tap count must be positive, got $taps" }
+    }
+
+    private val phases = if (taps == DEFAULT_TAPS) BS1770_PHASES else windowedSincPhases(taps)
+
+    // 每个声道保存两份历史，读取时不用取模
+    private val history = Array(channels) { FloatArray(taps * 2) }
+    private var historyIndex = 0
+    private var peak = 0f
+
+    val truePeak: Float get() = peak
+
+    fun process(samples: FloatArray) {
+        val frames = samples.size / channels
+
+        for (frame in 0 until frames) {
+            historyIndex = (historyIndex + 1) % taps
+
+            for (ch in 0 until channels) {
+                val h = history[ch]
+                val sample = samples[frame * channels + ch]
+                h[historyIndex] = sample
+                h[historyIndex + taps] = sample
+
+                // h[historyIndex + taps - k] 即 k 个样本之前的输入
+                for (phase in phases) {
+                    var y = 0f
+                    for (k in 0 until taps) y += phase[k] * h[historyIndex + taps - k]
+                    peak = maxOf(peak, abs(y))
+                }
+            }
+        }
+    }
+
+    fun reset() {
+        for (h in history) h.fill(0f)
+        historyIndex = 0
+        peak = 0f
+    }
+}
+
+/**
+ * 5.1 → 立体声下混，按 ITU-R BS.775 的系数：
+ * L' = L + 0.7071·C + 0.7071·Ls，R' = R + 0.7071·C + 0.7071·Rs，LFE 丢弃
+ *
+ * 输入按 L、R、C、LFE、Ls、Rs 交错排列（WAV / Android 的默认顺序）；双声道原样返回
+ * 不做归一化，满幅多声道内容下混后可能超过 1.0，测量响度时无需处理
+ */
+fun downmixToStereo(samples: FloatArray, channels: Int): FloatArray {
+    if (channels == 2) return samples
+    require(channels == 6) { "Only 5.1 (6 channels) can be downmixed, got $channels" }
+
+    val frames = samples.size / 6
+    val output = FloatArray(frames * 2)
+    val k = 0.70710677f // -3dB
+
+    for (frame in 0 until frames) {
+        val i = frame * 6
+        val center = samples[i + 2] * k
+        output[frame * 2] = samples[i] + center + samples[i + 4] * k
+        output[frame * 2 + 1] = samples[i + 1] + center + samples[i + 5] * k
+    }
+
+    return output
+}
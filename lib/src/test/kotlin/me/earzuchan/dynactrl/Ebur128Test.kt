@@ -0,0 +1,545 @@
+package me.earzuchan.dynactrl
+
+import me.earzuchan.dynactrl.models.ChannelType
+import me.earzuchan.dynactrl.models.InvalidReason
+import me.earzuchan.dynactrl.utils.CompleteKWeighting
+import org.junit.Assert.assertEquals
+import org.junit.Assert.assertFalse
+import org.junit.Assert.assertNotNull
+import org.junit.Assert.assertNotSame
+import org.junit.Assert.assertNull
+import org.junit.Assert.assertSame
+import org.junit.Assert.assertThrows
+import org.junit.Assert.assertTrue
+import org.junit.Test
+import kotlin.math.PI
+import kotlin.math.log10
+import kotlin.math.pow
+import kotlin.math.sin
+import kotlin.math.sqrt
+
+class Ebur128Test {
+    // 交错的正弦，各声道相同
+    private fun sine(
+        seconds: Float, amplitude: Float, channels: Int = 1, sampleRate: Int = 48000, freq: Float = 1000f
+    ): FloatArray {
+        val frames = (seconds * sampleRate).toInt()
+        return FloatArray(frames * channels) {
+            val frame = it / channels
+            (amplitude * sin(2.0 * PI * freq * frame / sampleRate)).toFloat()
+        }
+    }
+
+    // 分帧交替电平，用于制造有起伏的块能量
+    private fun alternating(seconds: Int, loud: Float, quiet: Float, sampleRate: Int = 48000): FloatArray =
+        (0 until seconds).map { sine(1f, if (it % 2 == 0) loud else quiet, sampleRate = sampleRate) }
+            .reduce { acc, part -> acc + part }
+
+    // 把左右两路单声道交错成立体声
+    private fun stereo(left: FloatArray, right: FloatArray): FloatArray =
+        FloatArray(left.size * 2) { if (it % 2 == 0) left[it / 2] else right[it / 2] }
+
+    @Test
+    fun quickLoudnessStaysNearFullMeasurement() {
+        val samples = sine(5f, 0.3f)
+        val full = LightweightEbuR128.quickLoudness(samples, 1, 48000, 1)
+
+        assertEquals(full, LightweightEbuR128.quickLoudness(samples, 1, 48000, 4), 1f)
+    }
+
+    @Test
+    fun tooLowSampleRateIsRejected() {
+        assertThrows(IllegalArgumentException::class.java) { LightweightEbuR128(1, 4) }
+        assertThrows(IllegalArgumentException::class.java) {
+            LightweightEbuR128.quickLoudness(FloatArray(100), 1, 48000, 12000)
+        }
+    }
+
+    @Test
+    fun snapshotMatchesLiveState() {
+        val analyzer = LightweightEbuR128(1, 48000)
+        repeat(10) { analyzer.addSamples(sine(0.3f, 0.2f)) }
+
+        val snapshot = analyzer.snapshot()
+        assertEquals(analyzer.getIntegratedLoudness(), snapshot.integratedLoudness, 0f)
+        assertEquals(analyzer.getSamplePeak(), snapshot.samplePeak, 0f)
+
+        // 之后的输入不影响已保存的快照
+        val blocks = snapshot.blockEnergies.size
+        analyzer.addSamples(sine(0.3f, 0.2f))
+        assertEquals(blocks, snapshot.blockEnergies.size)
+    }
+
+    @Test
+    fun snapshotCarriesRangeAndTruePeak() {
+        val analyzer = LightweightEbuR128(1, 48000, measureTruePeak = true).apply {
+            addSamples(sine(5f, 0.05f))
+            addSamples(sine(5f, 0.2f))
+        }
+        val snapshot = analyzer.snapshot()
+
+        assertEquals(analyzer.getLoudnessRange()!!, snapshot.lra!!, 0f)
+        assertEquals(analyzer.getTruePeak()!!, snapshot.truePeak!!, 0f)
+        assertEquals(snapshot.integratedLoudness, LightweightEbuR128.loudnessFromBlockEnergies(snapshot.blockEnergies), 0f)
+        assertEquals(snapshot, snapshot.copy())
+
+        // 未测量的字段为 null
+        val plain = LightweightEbuR128(1, 48000).apply { addSamples(sine(1f, 0.2f)) }.snapshot()
+        assertNull(plain.lra)
+        assertNull(plain.truePeak)
+    }
+
+    @Test
+    fun chunkedFeedMatchesSingleShot() {
+        val samples = sine(5f, 0.3f, channels = 2)
+
+        val single = LightweightEbuR128(2, 48000).apply { addSamples(samples) }
+
+        val chunked = LightweightEbuR128(2, 48000)
+        val chunkFrames = intArrayOf(1, 7, 333, 4410, 12345)
+        var offset = 0
+        var i = 0
+        while (offset < samples.size) {
+            val end = minOf(samples.size, offset + chunkFrames[i++ % chunkFrames.size] * 2)
+            chunked.addSamples(samples.copyOfRange(offset, end))
+            offset = end
+        }
+
+        assertEquals(single.getIntegratedLoudness(), chunked.getIntegratedLoudness(), 1e-4f)
+        assertEquals(single.getShortTermLoudness()!!, chunked.getShortTermLoudness()!!, 1e-4f)
+        assertEquals(single.snapshot().blockEnergies.size, chunked.snapshot().blockEnergies.size)
+        assertEquals(single.getSamplePeak(), chunked.getSamplePeak(), 0f)
+    }
+
+    @Test
+    fun int32InputMatchesFloatInput() {
+        val floats = sine(1f, 0.4f)
+        val ints = IntArray(floats.size) { (floats[it] * 2147483647.0).toInt() }
+
+        val fromInts = LightweightEbuR128(1, 48000).apply { addSamples(ints) }
+        val fromFloats = LightweightEbuR128(1, 48000).apply { addSamples(floats) }
+
+        assertEquals(fromFloats.getIntegratedLoudness(), fromInts.getIntegratedLoudness(), 1e-4f)
+    }
+
+    @Test
+    fun blockEnergyGatingMatchesLiveMeasurement() {
+        val analyzer = LightweightEbuR128(1, 48000).apply { addSamples(alternating(6, 0.1f, 0.01f)) }
+        val energies = analyzer.snapshot().blockEnergies
+
+        assertEquals(analyzer.getIntegratedLoudness(), LightweightEbuR128.loudnessFromBlockEnergies(energies), 0f)
+    }
+
+    @Test
+    fun gatingBlocksAdvanceByHop() {
+        val blocks = LightweightEbuR128(1, 48000).apply { addSamples(sine(1f, 0.2f)) }.getGatingBlocks()
+
+        // 1s 音频：0.4s 出第一个块，之后每 0.1s 一个
+        assertEquals(7, blocks.size)
+        assertEquals(0f, blocks[0].startSeconds, 0f)
+        for (i in blocks.indices) assertEquals(i * 0.1f, blocks[i].startSeconds, 1e-6f)
+    }
+
+    @Test
+    fun flushFilterStateKeepsBlocks() {
+        val analyzer = LightweightEbuR128(1, 48000).apply { addSamples(sine(1f, 0.3f)) }
+        val blocks = analyzer.getGatingBlocks()
+
+        analyzer.flushFilterState()
+        assertEquals(blocks, analyzer.getGatingBlocks())
+    }
+
+    @Test
+    fun lastBlockEnergyMatchesHandComputation() {
+        val analyzer = LightweightEbuR128(1, 48000)
+        assertNull(analyzer.getLastBlockEnergy())
+
+        // 恰好一个 400ms 块：能量即 K-weighting 输出的均方值
+        val samples = sine(0.4f, 0.5f)
+        analyzer.addSamples(samples)
+
+        val weighted = CompleteKWeighting(48000, 1).process(samples)
+        val expected = (weighted.sumOf { it.toDouble() * it } / weighted.size).toFloat()
+        assertEquals(expected, analyzer.getLastBlockEnergy()!!, expected * 1e-3f)
+    }
+
+    @Test
+    fun incrementalLoudnessMatchesSortedGating() {
+        val analyzer = LightweightEbuR128(1, 48000).apply { addSamples(alternating(10, 0.1f, 0.03f)) }
+
+        assertEquals(analyzer.getIntegratedLoudness(), analyzer.getIntegratedLoudnessIncremental(), 0.05f)
+    }
+
+    @Test
+    fun invalidReasonExplainsUnmeasurableInput() {
+        assertEquals(InvalidReason.NO_SAMPLES, LightweightEbuR128(1, 48000).getInvalidReason())
+        assertEquals(
+            InvalidReason.TOO_SHORT,
+            LightweightEbuR128(1, 48000).apply { addSamples(sine(0.2f, 0.5f)) }.getInvalidReason()
+        )
+        assertEquals(
+            InvalidReason.ALL_BELOW_GATE,
+            LightweightEbuR128(1, 48000).apply { addSamples(FloatArray(48000)) }.getInvalidReason()
+        )
+        assertNull(LightweightEbuR128(1, 48000).apply { addSamples(sine(1f, 0.5f)) }.getInvalidReason())
+    }
+
+    @Test
+    fun setSampleRateMeasuresAtNewRate() {
+        val analyzer = LightweightEbuR128(1, 44100).apply { addSamples(sine(1f, 0.5f, sampleRate = 44100)) }
+        analyzer.setSampleRate(48000)
+        analyzer.addSamples(sine(2f, 0.2f))
+
+        // 与一开始就按 48kHz 测量的结果一致：滤波器系数和块大小都已更新，切换前的块被丢弃
+        val fresh = LightweightEbuR128(1, 48000).apply { addSamples(sine(2f, 0.2f)) }
+        assertEquals(fresh.getIntegratedLoudness(), analyzer.getIntegratedLoudness(), 1e-4f)
+        assertEquals(fresh.snapshot().blockEnergies.size, analyzer.snapshot().blockEnergies.size)
+    }
+
+    @Test
+    fun stereoWidthSeparatesMonoFromWideMixes() {
+        val mono = LightweightEbuR128(2, 48000).apply { addSamples(sine(1f, 0.3f, channels = 2)) }
+        assertEquals(Float.NEGATIVE_INFINITY, mono.getStereoWidth()!!, 0f)
+
+        // 两声道频率不同（不相关），中、侧能量相当
+        val wide = LightweightEbuR128(2, 48000).apply { addSamples(stereo(sine(1f, 0.3f), sine(1f, 0.3f, freq = 1500f))) }
+        assertEquals(0f, wide.getStereoWidth()!!, 0.5f)
+
+        // 接近反相：侧能量远大于中能量
+        val left = sine(1f, 0.3f)
+        val antiphase = LightweightEbuR128(2, 48000).apply {
+            addSamples(stereo(left, FloatArray(left.size) { -0.9f * left[it] }))
+        }
+        assertTrue(antiphase.getStereoWidth()!! > 20f)
+
+        assertNull(LightweightEbuR128(1, 48000).apply { addSamples(sine(1f, 0.3f)) }.getStereoWidth())
+    }
+
+    @Test
+    fun singleBlockGivesBlockLoudness() {
+        val analyzer = LightweightEbuR128(1, 48000).apply { addSamples(sine(0.45f, 0.2f)) }
+
+        val blocks = analyzer.getGatingBlocks()
+        assertEquals(1, blocks.size)
+        assertEquals(10f * log10(blocks[0].energy) - 0.691f, analyzer.getIntegratedLoudness(), 1e-4f)
+        assertTrue(analyzer.isLowConfidence())
+    }
+
+    @Test
+    fun twoBlocksGateTheQuieterOnlyWhenFarApart() {
+        // 相差 20 dB：较弱的块被相对门限剔除
+        val farApart = LightweightEbuR128.loudnessFromBlockEnergies(listOf(1e-2f, 1e-4f))
+        assertEquals(10f * log10(1e-2f) - 0.691f, farApart, 1e-4f)
+
+        // 相差 3 dB：两块都保留
+        val close = LightweightEbuR128.loudnessFromBlockEnergies(listOf(1e-2f, 5e-3f))
+        assertEquals(10f * log10(7.5e-3f) - 0.691f, close, 1e-4f)
+    }
+
+    @Test
+    fun windowedLoudnessFollowsRecentLevel() {
+        val analyzer = LightweightEbuR128(1, 48000).apply {
+            addSamples(sine(10f, 0.05f))
+            addSamples(sine(10f, 0.1f))
+        }
+        val recentOnly = LightweightEbuR128(1, 48000).apply { addSamples(sine(10f, 0.1f)) }
+        val windowed = analyzer.getIntegratedLoudnessWindowed(5f)
+
+        // 最近 5s 只含较响的后半段；全程的结果被前半段拉低约 2 LU
+        assertEquals(recentOnly.getIntegratedLoudness(), windowed, 0.1f)
+        assertTrue(analyzer.getIntegratedLoudness() < windowed - 1.5f)
+
+        // 窗口比全程长时即综合响度
+        assertEquals(analyzer.getIntegratedLoudness(), analyzer.getIntegratedLoudnessWindowed(100f), 0f)
+    }
+
+    @Test
+    fun dcBlockingMatchesOffsetFreeSignal() {
+        val tone = sine(3f, 0.3f)
+        val offset = FloatArray(tone.size) { tone[it] + 0.5f }
+
+        val clean = LightweightEbuR128(1, 48000).apply { addSamples(tone) }
+        val blocked = LightweightEbuR128(1, 48000, dcBlock = true).apply { addSamples(offset) }
+
+        assertEquals(clean.getIntegratedLoudness(), blocked.getIntegratedLoudness(), 0.05f)
+    }
+
+    @Test
+    fun emptyInputIsNoOp() {
+        val analyzer = LightweightEbuR128(2, 48000)
+        analyzer.addSamples(FloatArray(0))
+        analyzer.addSamples(IntArray(0))
+
+        assertEquals(InvalidReason.NO_SAMPLES, analyzer.getInvalidReason())
+        assertEquals(0f, analyzer.getSamplePeak(), 0f)
+    }
+
+    @Test
+    fun partialFrameIsRejected() {
+        val analyzer = LightweightEbuR128(2, 48000)
+
+        assertThrows(IllegalArgumentException::class.java) { analyzer.addSamples(FloatArray(3)) }
+        assertThrows(IllegalArgumentException::class.java) { analyzer.addSamples(ShortArray(1)) }
+        assertEquals(InvalidReason.NO_SAMPLES, analyzer.getInvalidReason())
+    }
+
+    @Test
+    fun monoSumComparedWithStandardMeasurement() {
+        // 完全相关：下混与按声道计算的结果相同
+        val correlated = LightweightEbuR128(2, 48000, measureMonoSum = true).apply {
+            addSamples(sine(2f, 0.3f, channels = 2))
+        }
+        assertEquals(correlated.getIntegratedLoudness(), correlated.getMonoSumLoudness(), 0.01f)
+
+        // 不相关：下混约低 3 dB
+        val decorrelated = LightweightEbuR128(2, 48000, measureMonoSum = true).apply {
+            addSamples(stereo(sine(2f, 0.3f), sine(2f, 0.3f, freq = 1500f)))
+        }
+        assertEquals(decorrelated.getIntegratedLoudness() - 3.01f, decorrelated.getMonoSumLoudness(), 0.1f)
+
+        // 5.1 各声道相同：同样与按声道计算的结果一致
+        val tone = sine(2f, 0.1f)
+        val surroundSame = LightweightEbuR128(6, 48000, measureMonoSum = true).apply {
+            addSamples(FloatArray(tone.size * 6) { tone[it / 6] })
+        }
+        assertEquals(surroundSame.getIntegratedLoudness(), surroundSame.getMonoSumLoudness(), 0.01f)
+
+        // 5.1 五个主声道互不相关：下混低 -10·log10(Σw / (Σ√w)²) ≈ 6.96 dB
+        val tones = listOf(1000f, 1100f, 1200f, 0f, 1300f, 1400f).map { sine(2f, 0.1f, freq = it) }
+        val surroundMixed = LightweightEbuR128(6, 48000, measureMonoSum = true).apply {
+            addSamples(FloatArray(tone.size * 6) { tones[it % 6][it / 6] })
+        }
+        val weightSum = 3f + 2f * 1.41f
+        val gainSum = 3f + 2f * sqrt(1.41f)
+        assertEquals(
+            surroundMixed.getIntegratedLoudness() + 10f * log10(weightSum / (gainSum * gainSum)),
+            surroundMixed.getMonoSumLoudness(),
+            0.1f
+        )
+
+        // 5.1 中只有 LFE 有信号：LFE 不参与下混
+        val lfe = sine(2f, 0.3f)
+        val lfeOnly = LightweightEbuR128(6, 48000, measureMonoSum = true).apply {
+            addSamples(FloatArray(lfe.size * 6) { if (it % 6 == 3) lfe[it / 6] else 0f })
+        }
+        assertEquals(Float.NEGATIVE_INFINITY, lfeOnly.getMonoSumLoudness(), 0f)
+    }
+
+    @Test
+    fun loudnessRangeOfTwoLevelSignal() {
+        val quiet = 0.05f
+        val loud = quiet * 10f.pow(10f / 20f)
+        val analyzer = LightweightEbuR128(1, 48000).apply {
+            addSamples(sine(10f, quiet))
+            addSamples(sine(10f, loud))
+        }
+
+        assertEquals(10f, analyzer.getLoudnessRange()!!, 0.2f)
+    }
+
+    @Test
+    fun momentaryAndShortTermNeedFullWindows() {
+        val analyzer = LightweightEbuR128(1, 48000)
+
+        analyzer.addSamples(sine(0.3f, 0.2f))
+        assertNull(analyzer.getMomentaryLoudness())
+
+        analyzer.addSamples(sine(0.2f, 0.2f))
+        assertNotNull(analyzer.getMomentaryLoudness())
+        assertNull(analyzer.getShortTermLoudness())
+
+        // 10s 后段缓冲已多次回绕，稳态音调的三种读数一致
+        analyzer.addSamples(sine(9.5f, 0.2f))
+        assertEquals(analyzer.getIntegratedLoudness(), analyzer.getMomentaryLoudness()!!, 0.1f)
+        assertEquals(analyzer.getIntegratedLoudness(), analyzer.getShortTermLoudness()!!, 0.1f)
+    }
+
+    @Test
+    fun int16InputMatchesFloatInput() {
+        val floats = sine(2f, 0.4f)
+        val shorts = ShortArray(floats.size) { (floats[it] * 32767f).toInt().toShort() }
+
+        val fromShorts = LightweightEbuR128(1, 48000).apply { addSamples(shorts) }
+        val fromFloats = LightweightEbuR128(1, 48000).apply { addSamples(FloatArray(shorts.size) { shorts[it] / 32768f }) }
+
+        assertEquals(fromFloats.getIntegratedLoudness(), fromShorts.getIntegratedLoudness(), 1e-4f)
+    }
+
+    @Test
+    fun momentaryBallisticsLagBehindStep() {
+        val analyzer = LightweightEbuR128(1, 48000).apply {
+            setMomentaryBallistics(1f, 1f)
+            addSamples(sine(2f, 0.01f))
+        }
+        val quietLevel = analyzer.getMomentaryLoudnessSmoothed()!!
+
+        analyzer.addSamples(sine(0.5f, 0.1f))
+        val raw = analyzer.getMomentaryLoudness()!!
+        val smoothed = analyzer.getMomentaryLoudnessSmoothed()!!
+
+        // 原始值已跳到 +20 dB，平滑值只走了一部分
+        assertTrue(smoothed > quietLevel + 1f)
+        assertTrue(smoothed < raw - 5f)
+    }
+
+    @Test
+    fun momentarySmoothedDefaultsToRaw() {
+        val analyzer = LightweightEbuR128(1, 48000).apply { addSamples(alternating(3, 0.1f, 0.01f)) }
+
+        assertEquals(analyzer.getMomentaryLoudness()!!, analyzer.getMomentaryLoudnessSmoothed()!!, 1e-4f)
+    }
+
+    @Test
+    fun reconfigureReusesBuffersForSameFormat() {
+        val field = LightweightEbuR128::class.java.getDeclaredField("circularBuffer").apply { isAccessible = true }
+        val analyzer = LightweightEbuR128(2, 48000).apply { addSamples(sine(1f, 0.2f, channels = 2)) }
+
+        val before = field.get(analyzer)
+        analyzer.reconfigure(2, 48000)
+        assertSame(before, field.get(analyzer))
+        assertEquals(Float.NEGATIVE_INFINITY, analyzer.getIntegratedLoudness(), 0f)
+
+        analyzer.reconfigure(2, 44100)
+        assertNotSame(before, field.get(analyzer))
+    }
+
+    @Test
+    fun resetDropsPreviousTrack() {
+        val analyzer = LightweightEbuR128(1, 48000).apply { addSamples(sine(3f, 0.5f)) }
+
+        analyzer.reset()
+        analyzer.addSamples(FloatArray(96000))
+
+        assertEquals(Float.NEGATIVE_INFINITY, analyzer.getIntegratedLoudness(), 0f)
+        assertEquals(0f, analyzer.getSamplePeak(), 0f)
+    }
+
+    @Test
+    fun invertedChannelPairIsFlagged() {
+        val left = sine(1f, 0.3f)
+        val inverted = stereo(left, FloatArray(left.size) { -left[it] })
+
+        assertEquals(listOf(0 to 1), LightweightEbuR128(2, 48000).apply { addSamples(inverted) }.getInvertedChannelPairs())
+        assertTrue(
+            LightweightEbuR128(2, 48000).apply { addSamples(sine(1f, 0.3f, channels = 2)) }.getInvertedChannelPairs().isEmpty()
+        )
+    }
+
+    @Test
+    fun shortTermCadenceAt44100() {
+        val analyzer = LightweightEbuR128(1, 44100)
+        val samples = sine(10f, 0.2f, sampleRate = 44100)
+
+        var shortTermBlocks = 0
+        for (start in samples.indices step 1000)
+            shortTermBlocks += analyzer.addSamples(samples.copyOfRange(start, minOf(samples.size, start + 1000))).shortTermBlocks
+
+        // 3s 出第一个，之后每 1s 一个
+        assertEquals(8, shortTermBlocks)
+        assertNotNull(analyzer.getShortTermLoudness())
+    }
+
+    @Test
+    fun channelTypesWeightBlockEnergy() {
+        val tone = sine(2f, 0.2f)
+        fun onlyOn(channel: Int) = FloatArray(tone.size * 6) { if (it % 6 == channel) tone[it / 6] else 0f }
+
+        // 5.1 默认布局：环绕声道 +1.5 dB，LFE 不计入
+        val front = LightweightEbuR128(6, 48000).apply { addSamples(onlyOn(0)) }.getIntegratedLoudness()
+        val surround = LightweightEbuR128(6, 48000).apply { addSamples(onlyOn(4)) }.getIntegratedLoudness()
+        assertEquals(10f * log10(1.41f), surround - front, 0.01f)
+        assertEquals(
+            InvalidReason.ALL_BELOW_GATE,
+            LightweightEbuR128(6, 48000).apply { addSamples(onlyOn(3)) }.getInvalidReason()
+        )
+
+        // 自定义顺序：把 0 号声道标为 LFE
+        val remapped = LightweightEbuR128(6, 48000).apply {
+            setChannelMap(
+                listOf(
+                    ChannelType.LFE, ChannelType.RIGHT, ChannelType.CENTER,
+                    ChannelType.LEFT, ChannelType.LEFT_SURROUND, ChannelType.RIGHT_SURROUND
+                )
+            )
+            addSamples(onlyOn(0))
+        }
+        assertEquals(Float.NEGATIVE_INFINITY, remapped.getIntegratedLoudness(), 0f)
+    }
+
+    @Test
+    fun surroundWeightingCanBeDisabled() {
+        val tone = sine(2f, 0.2f)
+        val surroundOnly = FloatArray(tone.size * 6) { if (it % 6 == 4) tone[it / 6] else 0f }
+
+        val weighted = LightweightEbuR128(6, 48000).apply { addSamples(surroundOnly) }.getIntegratedLoudness()
+        val unweighted = LightweightEbuR128(6, 48000, weightSurround = false).apply { addSamples(surroundOnly) }
+            .getIntegratedLoudness()
+        assertEquals(10f * log10(1.41f), weighted - unweighted, 0.01f)
+
+        // 关闭后环绕声道与前置声道等权
+        val front = LightweightEbuR128(6, 48000).apply {
+            addSamples(FloatArray(tone.size * 6) { if (it % 6 == 0) tone[it / 6] else 0f })
+        }
+        assertEquals(front.getIntegratedLoudness(), unweighted, 1e-4f)
+    }
+
+    @Test
+    fun channelMapChangeRestartsMeasurement() {
+        val tone = sine(2f, 0.2f)
+        val frontLeft = FloatArray(tone.size * 6) { if (it % 6 == 0) tone[it / 6] else 0f }
+        val surroundMap = listOf(
+            ChannelType.LEFT_SURROUND, ChannelType.RIGHT, ChannelType.CENTER,
+            ChannelType.LFE, ChannelType.LEFT_SURROUND, ChannelType.RIGHT_SURROUND
+        )
+
+        // 与默认布局相同的映射不影响已有的块
+        val analyzer = LightweightEbuR128(6, 48000).apply { addSamples(frontLeft) }
+        val blocks = analyzer.getGatingBlocks().size
+        analyzer.setChannelMap(ChannelType.defaultLayout(6)!!)
+        assertEquals(blocks, analyzer.getGatingBlocks().size)
+
+        // 权重变化后只统计之后的输入
+        analyzer.setChannelMap(surroundMap)
+        analyzer.addSamples(frontLeft)
+        val fresh = LightweightEbuR128(6, 48000).apply {
+            setChannelMap(surroundMap)
+            addSamples(frontLeft)
+        }
+        assertEquals(fresh.getIntegratedLoudness(), analyzer.getIntegratedLoudness(), 1e-4f)
+        assertEquals(blocks, analyzer.getGatingBlocks().size)
+    }
+
+    @Test
+    fun channelMapMustMatchChannelCount() {
+        assertThrows(IllegalArgumentException::class.java) {
+            LightweightEbuR128(6, 48000).setChannelMap(listOf(ChannelType.LEFT, ChannelType.RIGHT))
+        }
+    }
+
+    @Test
+    fun segmentDeltaMatchesLevelStep() {
+        val quiet = 0.1f
+        val analyzer = LightweightEbuR128(1, 48000).apply {
+            addSamples(sine(5f, quiet))
+            addSamples(sine(5f, quiet * 10f.pow(6f / 20f)))
+        }
+
+        assertEquals(6f, analyzer.getSegmentLoudnessDelta(0f to 5f, 5f to 10f)!!, 0.1f)
+    }
+
+    @Test
+    fun addSamplesReportsCompletedBlocks() {
+        val analyzer = LightweightEbuR128(1, 48000)
+
+        // 0.4s 出第一个门控块，之后每 0.1s 一个
+        val first = analyzer.addSamples(sine(1f, 0.2f))
+        assertEquals(7, first.integratedBlocks)
+        assertEquals(0, first.shortTermBlocks)
+
+        val second = analyzer.addSamples(sine(2f, 0.2f))
+        assertEquals(20, second.integratedBlocks)
+        assertEquals(1, second.shortTermBlocks)
+
+        assertFalse(analyzer.addSamples(FloatArray(0)).hasNewBlocks)
+    }
+}
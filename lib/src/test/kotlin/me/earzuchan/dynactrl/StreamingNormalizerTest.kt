@@ -0,0 +1,34 @@
+package me.earzuchan.dynactrl
+
+import org.junit.Assert.assertEquals
+import org.junit.Assert.assertThrows
+import org.junit.Test
+import kotlin.math.PI
+import kotlin.math.pow
+import kotlin.math.sin
+
+class StreamingNormalizerTest {
+    @Test
+    fun gainConvergesTowardTarget() {
+        val sampleRate = 48000
+        val normalizer = StreamingNormalizer(1, sampleRate, -20f, smoothingTime = 0.5f)
+
+        // 20s 稳态音调，每次 100ms
+        repeat(200) { chunk ->
+            val samples = FloatArray(4800) {
+                (0.05 * sin(2.0 * PI * 1000.0 * (chunk * 4800 + it) / sampleRate)).toFloat()
+            }
+            normalizer.process(samples)
+        }
+
+        val expectedGain = 10f.pow((-20f - normalizer.getMeasuredLoudness()) / 20f)
+        assertEquals(expectedGain, normalizer.currentGain, expectedGain * 0.02f)
+    }
+
+    @Test
+    fun partialFrameIsRejected() {
+        val normalizer = StreamingNormalizer(2, 48000, -14f)
+
+        assertThrows(IllegalArgumentException::class.java) { normalizer.process(FloatArray(5)) }
+    }
+}
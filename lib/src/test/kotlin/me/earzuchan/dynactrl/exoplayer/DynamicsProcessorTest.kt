@@ -0,0 +1,142 @@
+package me.earzuchan.dynactrl.exoplayer
+
+import androidx.annotation.OptIn
+import androidx.media3.common.C
+import androidx.media3.common.audio.AudioProcessor
+import androidx.media3.common.util.UnstableApi
+import me.earzuchan.dynactrl.LightweightEbuR128
+import me.earzuchan.dynactrl.models.AudioLoudnessInfo
+import org.junit.Assert.assertEquals
+import org.junit.Assert.assertFalse
+import org.junit.Assert.assertTrue
+import org.junit.Test
+import java.nio.ByteBuffer
+import java.nio.ByteOrder
+import kotlin.math.PI
+import kotlin.math.abs
+import kotlin.math.pow
+import kotlin.math.sin
+
+@OptIn(UnstableApi::class)
+class DynamicsProcessorTest {
+    // 按 48kHz 浮点 PCM 配置处理器并处理一个缓冲区，返回输出样本
+    private fun process(processor: DynamicsProcessor, samples: FloatArray, channels: Int = 1): FloatArray {
+        processor.configure(AudioProcessor.AudioFormat(48000, channels, C.ENCODING_PCM_FLOAT))
+
+        val input = ByteBuffer.allocateDirect(samples.size * 4).order(ByteOrder.nativeOrder())
+        input.asFloatBuffer().put(samples)
+        processor.queueInput(input)
+
+        val output = processor.output.order(ByteOrder.nativeOrder())
+        return FloatArray(output.remaining() / 4) { output.float }
+    }
+
+    @Test
+    fun lookaheadLimiterCatchesBoostedPeaks() {
+        // -26 LUFS 归一化到 -14 LUFS 需要 +12 dB，0.5 的峰值会被推到约 2.0
+        val processor = DynamicsProcessor(lookaheadTime = 0.005f)
+        processor.setCurrentTrackLoudness(AudioLoudnessInfo(-26f))
+
+        val output = process(processor, FloatArray(48000) { (0.5 * sin(2.0 * PI * 1000.0 * it / 48000)).toFloat() })
+        assertTrue(output.all { abs(it) <= 0.95f + 1e-6f })
+    }
+
+    @Test
+    fun lookaheadTailIsDrainedAtEndOfStream() {
+        val processor = DynamicsProcessor(lookaheadTime = 0.005f)
+        val output = process(processor, FloatArray(4800) { 0.5f })
+
+        // 5ms 前视：输出晚 239 帧，开头是延迟线里的静音
+        assertEquals(239, processor.getLatencyFrames())
+        assertEquals(0f, output[0], 0f)
+
+        processor.queueEndOfStream()
+        val tail = processor.output.order(ByteOrder.nativeOrder())
+        assertEquals(239 * 4, tail.remaining())
+        assertEquals(0.5f, tail.getFloat(tail.limit() - 4), 1e-6f)
+    }
+
+    @Test
+    fun int32InputStaysInteger() {
+        // -20 LUFS 到 -14 LUFS：+6 dB；即时限制器和前视限制器两条路径都按整数读写
+        for (lookaheadTime in floatArrayOf(0f, 0.005f)) {
+            val processor = DynamicsProcessor(lookaheadTime = lookaheadTime)
+            processor.setCurrentTrackLoudness(AudioLoudnessInfo(-20f))
+            processor.configure(AudioProcessor.AudioFormat(48000, 1, C.ENCODING_PCM_32BIT))
+
+            val input = ByteBuffer.allocateDirect(480 * 4).order(ByteOrder.nativeOrder())
+            repeat(480) { input.putInt((0.1 * 2147483648.0).toInt()) }
+            input.flip()
+            processor.queueInput(input)
+
+            val output = processor.output.order(ByteOrder.nativeOrder())
+            val last = output.getInt(output.limit() - 4) / 2147483648.0
+            assertEquals(0.1 * 10.0.pow(6.0 / 20.0), last, 1e-6)
+        }
+    }
+
+    @Test
+    fun measureOutputTracksProcessedLoudness() {
+        val processor = DynamicsProcessor(measureOutput = true)
+        val input = FloatArray(2 * 48000 * 2) { (0.3 * sin(2.0 * PI * 1000.0 * (it / 2) / 48000)).toFloat() }
+        process(processor, input, channels = 2)
+
+        // 没有响度信息时增益为 1，输出即输入
+        val reference = LightweightEbuR128(2, 48000).apply { addSamples(input) }
+        assertEquals(reference.getIntegratedLoudness(), processor.getLiveLoudness(), 0.1f)
+    }
+
+    @Test
+    fun peakCeilingReducesGainForLoudPeaks() {
+        // -30 LUFS 到 -14 LUFS 需要 +16 dB，但 0.9 的峰值离默认的 -1 dB 天花板只剩不到 0.1 dB
+        val processor = DynamicsProcessor()
+        processor.setCurrentTrackLoudness(AudioLoudnessInfo(-30f, samplePeak = 0.9f))
+        assertTrue(processor.isPeakLimited())
+
+        val output = process(processor, FloatArray(480) { 0.05f })
+        assertEquals(0.05f * 10f.pow(-1f / 20f) / 0.9f, output.last(), 1e-6f)
+
+        // 显式关闭天花板后按目标响度给满增益
+        val uncapped = DynamicsProcessor(peakCeilingDb = null)
+        uncapped.setCurrentTrackLoudness(AudioLoudnessInfo(-30f, samplePeak = 0.9f))
+        assertFalse(uncapped.isPeakLimited())
+        assertEquals(0.05f * 10f.pow(16f / 20f), process(uncapped, FloatArray(480) { 0.05f }).last(), 1e-5f)
+    }
+
+    @Test
+    fun loudTrackIsAttenuatedTowardTarget() {
+        val processor = DynamicsProcessor(targetLufs = -23f)
+        processor.setCurrentTrackLoudness(AudioLoudnessInfo(-13f, samplePeak = 0.5f))
+
+        val output = process(processor, FloatArray(480) { 0.2f })
+        assertEquals(0.2f * 10f.pow(-10f / 20f), output.last(), 1e-6f)
+    }
+
+    @Test
+    fun silentTrackKeepsUnityGain() {
+        for (lufs in floatArrayOf(-70f, -90f, Float.NEGATIVE_INFINITY)) {
+            val processor = DynamicsProcessor(targetLufs = -23f)
+            processor.setCurrentTrackLoudness(AudioLoudnessInfo(lufs))
+
+            assertFalse(processor.isActive())
+            assertEquals(0.2f, process(processor, FloatArray(480) { 0.2f }).last(), 0f)
+        }
+    }
+
+    @Test
+    fun defaultLimiterLeavesPeaksBelowThresholdUntouched() {
+        // 默认硬拐点：0.9 低于 0.95 的阈值，原样输出（3 dB 软拐点会从约 0.8 开始压缩）
+        val output = process(DynamicsProcessor(), FloatArray(480) { 0.9f })
+
+        assertTrue(output.all { it == 0.9f })
+    }
+
+    @Test
+    fun lookaheadLimiterHoldsConfiguredThreshold() {
+        val processor = DynamicsProcessor(lookaheadTime = 0.005f, limiterThreshold = 0.5f, limiterKneeDb = 6f)
+        val output = process(processor, FloatArray(48000) { (0.9 * sin(2.0 * PI * 1000.0 * it / 48000)).toFloat() })
+
+        assertTrue(output.all { abs(it) <= 0.5f + 1e-6f })
+        assertTrue(output.maxOf { abs(it) } > 0.4f)
+    }
+}
@@ -0,0 +1,97 @@
+package me.earzuchan.dynactrl.models
+
+import org.junit.Assert.assertEquals
+import org.junit.Assert.assertFalse
+import org.junit.Assert.assertNull
+import org.junit.Assert.assertTrue
+import org.junit.Test
+import kotlin.math.log10
+import kotlin.math.pow
+
+class AudioLoudnessInfoTest {
+    private val failed = AudioLoudnessInfo(-70f, invalidReason = InvalidReason.UNREADABLE)
+
+    @Test
+    fun replayGainIsRelativeToReference() {
+        val info = AudioLoudnessInfo(-23.45f, samplePeak = 0.5f)
+
+        assertEquals(5.45f, info.replayGainTrackGain()!!, 1e-4f)
+        assertEquals(0.5f, info.replayGainTrackPeak(), 0f)
+        assertNull(failed.replayGainTrackGain())
+    }
+
+    @Test
+    fun albumGainClipsAgainstTruePeakCeiling() {
+        val tracks = listOf(
+            AudioLoudnessInfo(-20f, samplePeak = 0.5f, truePeak = 0.9f),
+            AudioLoudnessInfo(-20f, samplePeak = 0.2f, truePeak = 0.25f),
+            failed
+        )
+        val album = AudioLoudnessInfo.albumGain(tracks, -14f)
+
+        assertEquals(-20f, album.albumLufs, 1e-3f)
+        assertEquals(6f, album.gainDb, 1e-3f)
+        // 0.9 ≈ -0.915 dBTP，离 -1 dBTP 天花板已不足 0.1 dB
+        assertEquals(-1f - 20f * log10(0.9f), album.peakSafeGainDb, 1e-3f)
+        assertEquals(listOf(0), album.clippingTracks)
+    }
+
+    @Test
+    fun excessiveGainAboveLimit() {
+        assertTrue(AudioLoudnessInfo(-40f).isExcessiveGain(-14f))
+        assertFalse(AudioLoudnessInfo(-30f).isExcessiveGain(-14f))
+        assertFalse(AudioLoudnessInfo(-40f).isExcessiveGain(-14f, maxGainDb = 30f))
+
+        // 失败的分析不会被归一化
+        assertFalse(failed.isExcessiveGain(-14f))
+    }
+
+    @Test
+    fun millibelRoundsToHundredthsOfLu() {
+        assertEquals(-2345, AudioLoudnessInfo(-23.45f).lufsMillibel())
+        assertEquals(AudioLoudnessInfo.UNMEASURABLE_MILLIBEL, AudioLoudnessInfo(Float.NEGATIVE_INFINITY).lufsMillibel())
+
+        // 失败时的 -70 是占位值，不是测量结果
+        assertFalse(failed.isMeasurable())
+        assertEquals(AudioLoudnessInfo.UNMEASURABLE_MILLIBEL, failed.lufsMillibel())
+    }
+
+    @Test
+    fun headroomToPeakCeiling() {
+        val info = AudioLoudnessInfo(-20f, samplePeak = 10f.pow(-6f / 20f))
+
+        assertEquals(5f, info.peakHeadroomDb(-1f), 1e-3f)
+        assertEquals(-1f, info.peakHeadroomDb(-7f), 1e-3f)
+        assertEquals(Float.POSITIVE_INFINITY, AudioLoudnessInfo(-20f).peakHeadroomDb(-1f), 0f)
+    }
+
+    @Test
+    fun dynamicsScoreRanksDynamicMasterAboveBrickwall() {
+        // 古典录音：LRA 15 LU，峰值 -1 dB，综合 -23 LUFS（PLR 22 dB）
+        val classical = AudioLoudnessInfo(-23f, samplePeak = 10f.pow(-1f / 20f), lra = 15f)
+        // 砖墙限幅的流行母带：LRA 3 LU，峰值 -0.1 dB，综合 -7 LUFS（PLR 6.9 dB）
+        val brickwalled = AudioLoudnessInfo(-7f, samplePeak = 10f.pow(-0.1f / 20f), lra = 3f)
+
+        assertEquals(87.5f, classical.dynamicsScore()!!, 0.1f)
+        assertTrue(brickwalled.dynamicsScore()!! < 15f)
+        assertNull(AudioLoudnessInfo(-23f, samplePeak = 0.5f).dynamicsScore())
+    }
+
+    @Test
+    fun peakPrefersTruePeak() {
+        assertEquals(-6.02f, AudioLoudnessInfo(-20f, samplePeak = 0.5f).peakDb()!!, 0.01f)
+        assertEquals(20f * log10(0.9f), AudioLoudnessInfo(-20f, samplePeak = 0.5f, truePeak = 0.9f).peakDb()!!, 1e-4f)
+        assertNull(AudioLoudnessInfo(-20f).peakDb())
+    }
+
+    @Test
+    fun truePeakLimitNeedsMeasuredTruePeak() {
+        // -0.46 dBTP 超过 -1 dBTP，低于 0 dBTP
+        val info = AudioLoudnessInfo(-10f, samplePeak = 0.8f, truePeak = 0.95f)
+        assertTrue(info.exceedsTruePeakLimit())
+        assertFalse(info.exceedsTruePeakLimit(0f))
+
+        // 没有真峰值时不拿采样峰值顶替
+        assertFalse(AudioLoudnessInfo(-10f, samplePeak = 0.99f).exceedsTruePeakLimit())
+    }
+}
@@ -0,0 +1,120 @@
+package me.earzuchan.dynactrl.utils
+
+import org.junit.Assert.assertArrayEquals
+import org.junit.Assert.assertEquals
+import org.junit.Assert.assertThrows
+import org.junit.Assert.assertTrue
+import org.junit.Test
+import kotlin.math.PI
+import kotlin.math.abs
+import kotlin.math.exp
+import kotlin.math.sin
+
+class UtilsTest {
+    @Test
+    fun circularBufferWrapsAround() {
+        val buffer = CircularBuffer(4)
+        buffer.addAll(floatArrayOf(1f, 2f, 3f))
+        buffer.removeFirst(2)
+        buffer.addAll(floatArrayOf(4f, 5f, 6f))
+
+        assertEquals(4, buffer.size)
+        assertEquals(0, buffer.freeSpace)
+        assertArrayEquals(floatArrayOf(3f, 4f, 5f, 6f), FloatArray(4) { buffer.get(it) }, 0f)
+        assertThrows(IndexOutOfBoundsException::class.java) { buffer.get(4) }
+
+        // 只写入区间 [1, 3)
+        buffer.removeFirst(4)
+        buffer.addAll(floatArrayOf(7f, 8f, 9f), 1, 3)
+        assertArrayEquals(floatArrayOf(8f, 9f), FloatArray(buffer.size) { buffer.get(it) }, 0f)
+
+        buffer.clear()
+        assertEquals(0, buffer.size)
+        assertEquals(4, buffer.freeSpace)
+    }
+
+    @Test
+    fun lookaheadLimiterStaysBelowThreshold() {
+        val sampleRate = 48000
+        val threshold = 0.9
+        val releaseCoeff = exp(-1.0 / (0.01 * sampleRate))
+
+        for (kneeDb in doubleArrayOf(0.0, 3.0)) {
+            val limiter = LookaheadLimiter(2, 240, threshold, releaseCoeff, kneeDb)
+            val frame = DoubleArray(2)
+
+            // 2 倍过载的正弦，每 100ms 在大小电平间切换，再叠加孤立的尖峰
+            for (i in 0 until sampleRate) {
+                val level = if ((i / 4800) % 2 == 0) 0.2 else 1.0
+                val sample = if (i % 3001 == 0) 4.0 else 2.0 * level * sin(2.0 * PI * 1000.0 * i / sampleRate)
+                frame[0] = sample
+                frame[1] = -sample
+                limiter.process(frame)
+
+                assertTrue(abs(frame[0]) <= threshold + 1e-9)
+                assertTrue(abs(frame[1]) <= threshold + 1e-9)
+            }
+        }
+    }
+
+    @Test
+    fun kWeightingResetClearsHistory() {
+        val tone = FloatArray(4800) { sin(2.0 * PI * 1000.0 * it / 48000).toFloat() }
+        val filter = CompleteKWeighting(48000, 1).apply { process(tone) }
+
+        // 不清空时，之前的滤波器记忆让静音输入也有输出
+        assertTrue(filter.process(FloatArray(16)).any { it != 0f })
+
+        filter.reset()
+        assertTrue(filter.process(FloatArray(16)).all { it == 0f })
+    }
+
+    @Test
+    fun truePeakFindsInterSamplePeak() {
+        // fs/4 正弦、相位 45°：采样点都落在 ±0.707，真实波峰为 1.0
+        val meter = TruePeakMeter(1)
+        val samples = FloatArray(4000) { (sin(PI / 2 * it + PI / 4)).toFloat() }
+        meter.process(samples)
+
+        assertEquals(0.7071f, samples.maxOf { abs(it) }, 1e-3f)
+        assertEquals(1f, meter.truePeak, 0.02f)
+    }
+
+    @Test
+    fun longerTruePeakFilterIsMoreAccurateNearNyquist() {
+        // 0.45·fs 的正弦（淡入避免起始瞬态），真实波峰为 1.0
+        val samples = FloatArray(3000) { (sin(2.0 * PI * 0.45 * it + 0.3) * minOf(1.0, it / 500.0)).toFloat() }
+        val short = TruePeakMeter(1).apply { process(samples) }.truePeak
+        val long = TruePeakMeter(1, taps = 48).apply { process(samples) }.truePeak
+
+        assertTrue(abs(1f - long) < abs(1f - short))
+        assertEquals(1f, long, 0.01f)
+    }
+
+    @Test
+    fun centerOnlyDownmixesEquallyToBothSides() {
+        val surround = FloatArray(6 * 100) { if (it % 6 == 2) 0.5f else 0f }
+        val stereo = downmixToStereo(surround, 6)
+
+        assertEquals(200, stereo.size)
+        for (sample in stereo) assertEquals(0.5f * 0.70710677f, sample, 1e-6f)
+    }
+
+    @Test
+    fun softKneeNeverExceedsThreshold() {
+        val threshold = 0.9
+        var lastOutput = 0.0
+
+        for (i in 1..400) {
+            val peak = i * 0.01
+            val output = peak * softKneeGain(peak, threshold, 6.0)
+
+            assertTrue(output <= threshold + 1e-12)
+            assertTrue(output >= lastOutput - 1e-12) // 单调
+            lastOutput = output
+        }
+
+        // 拐点以下不压缩
+        assertEquals(1.0, softKneeGain(0.5, threshold, 6.0), 0.0)
+    }
+}